@@ -0,0 +1,18 @@
+#![no_main]
+use http_server::http::parser::{is_token, parse_head};
+use http_server::http::request::HttpRequest;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((line, fields)) = parse_head(data) {
+        assert!(is_token(line.method));
+        assert!(!line.target.bytes().any(|byte| byte <= b' ' || byte == 0x7f));
+        for field in fields {
+            assert!(is_token(field.name));
+            assert!(!field.value.iter().any(|&byte| byte == b'\r' || byte == b'\n'));
+            assert!(field.to_header().is_ok());
+        }
+    }
+
+    let _ = HttpRequest::from_bytes(data);
+});
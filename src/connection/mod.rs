@@ -1,2 +1,4 @@
+/// Handle used to stop servers gracefully
+pub mod shutdown;
 /// TCP connection implementation
 pub mod tcp;
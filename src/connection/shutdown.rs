@@ -0,0 +1,87 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Handle used to stop a server gracefully, e.g. from a signal handler or another thread.
+///
+/// When shutdown is requested, the connection stops accepting clients, lets the requests being
+/// served finish and closes idle persistent connections. Clones of a handle share their state.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ShutdownHandle {
+    /// Creates a new [`ShutdownHandle`] for which shutdown is not requested.
+    pub fn new() -> ShutdownHandle {
+        ShutdownHandle::default()
+    }
+
+    /// Requests the shutdown of the server. Returns immediately, the server stops in the
+    /// background.
+    pub fn shutdown(&self) {
+        let (requested, changed) = &*self.state;
+        *requested.lock().unwrap_or_else(|error| error.into_inner()) = true;
+        changed.notify_all();
+    }
+
+    /// Returns true once shutdown is requested.
+    pub fn is_requested(&self) -> bool {
+        *self
+            .state
+            .0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Waits until shutdown is requested or the timeout elapses. Returns true if shutdown is
+    /// requested.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let (requested, changed) = &*self.state;
+        let deadline = Instant::now() + timeout;
+        let mut requested = requested.lock().unwrap_or_else(|error| error.into_inner());
+        while !*requested {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            requested = changed
+                .wait_timeout(requested, deadline - now)
+                .unwrap_or_else(|error| error.into_inner())
+                .0;
+        }
+        *requested
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn share_state_between_clones() {
+        let handle = ShutdownHandle::new();
+        let clone = handle.clone();
+
+        assert!(!clone.is_requested());
+        handle.shutdown();
+
+        assert!(clone.is_requested());
+        assert!(clone.wait_timeout(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn wake_up_waiting_threads() {
+        let handle = ShutdownHandle::new();
+        let clone = handle.clone();
+        let start = Instant::now();
+
+        let waiting = thread::spawn(move || clone.wait_timeout(Duration::from_secs(10)));
+        thread::sleep(Duration::from_millis(20));
+        handle.shutdown();
+
+        assert!(waiting.join().unwrap());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(!ShutdownHandle::new().wait_timeout(Duration::from_millis(1)));
+    }
+}
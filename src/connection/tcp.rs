@@ -1,75 +1,495 @@
-use crate::http::server::{Connection, ServerError};
-use crate::thread::pool::ThreadPool;
+use crate::connection::shutdown::ShutdownHandle;
+use crate::http::access_log::{AccessLog, AccessLogEntry};
+use crate::http::reader::{ReadError, RequestLimits, RequestReader};
+use crate::http::request::{HttpRequest, HttpVersion};
+use crate::http::response::{HttpResponse, WriteOptions, SERVER_NAME};
+use crate::http::server::{build_error_response, Connection, Handler, ServerError};
+use crate::thread::pool::{ThreadPool, ThreadPoolConfig};
+use http::header::{HeaderValue, CONNECTION, RETRY_AFTER, SERVER};
+use http::StatusCode;
+use std::collections::HashMap;
 use std::io;
 use std::io::{Read, Write};
-use std::net::{SocketAddr, TcpListener};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Span};
+
+/// Identifier given to the next request, unique within the process
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Longest wait for new clients before checking whether shutdown is requested
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Longest time spent writing the response to a client rejected because the server is busy
+const REJECT_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Maximum number of bytes of a rejected client read before closing its connection
+const REJECT_DRAIN_SIZE: usize = 64 * 1024;
+
+/// Configuration of a [`TcpServerConnection`]
+#[derive(Debug, Clone)]
+pub struct TcpServerConfig {
+    /// Limits on the size of incoming requests
+    pub limits: RequestLimits,
+    /// Time a persistent connection may stay idle before being closed. It also bounds the wait
+    /// between two reads of the same request. `None` disables the timeout.
+    pub keep_alive_timeout: Option<Duration>,
+    /// Maximum number of requests served on a single connection before it is closed
+    pub max_requests: usize,
+    /// Value of the `Server` header added to responses which do not set one
+    pub server_name: HeaderValue,
+    /// Options used to write responses, including the size of the buffer which bounds the memory
+    /// used to send a body
+    pub write_options: WriteOptions,
+    /// Log receiving a line for each response, including the ones to invalid requests and to
+    /// clients rejected while the server is busy. `None` disables the access log.
+    pub access_log: Option<Arc<AccessLog>>,
+    /// Time given to the requests being served to complete once shutdown is requested. Their
+    /// connections are closed after this delay.
+    pub shutdown_timeout: Duration,
+    /// Threads serving the connections. Clients accepted while all the workers are busy and the
+    /// queue is full are answered `503 Service Unavailable`.
+    pub thread_pool: ThreadPoolConfig,
+    /// Delay after which rejected clients are invited to retry, sent in `Retry-After`
+    pub retry_after: Duration,
+}
+
+impl Default for TcpServerConfig {
+    fn default() -> Self {
+        TcpServerConfig {
+            limits: RequestLimits::default(),
+            keep_alive_timeout: Some(Duration::from_secs(5)),
+            max_requests: 100,
+            server_name: HeaderValue::from_static(SERVER_NAME),
+            write_options: WriteOptions::default(),
+            access_log: None,
+            shutdown_timeout: Duration::from_secs(10),
+            thread_pool: ThreadPoolConfig::default(),
+            retry_after: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Client served on a connection
+#[derive(Default)]
+struct Client<'a> {
+    /// Address of the client, if known
+    remote_addr: Option<IpAddr>,
+    /// Registry in which the connection is tracked, with its identifier
+    tracking: Option<(&'a OpenConnections, u64)>,
+}
+
+impl Client<'_> {
+    /// Records whether a request is being served. Returns false if the connection should be
+    /// closed instead of waiting for another request.
+    fn set_busy(&self, busy: bool) -> bool {
+        self.tracking
+            .is_none_or(|(connections, id)| connections.set_busy(id, busy))
+    }
+
+    /// Returns true if the server is shutting down.
+    fn shutdown_requested(&self) -> bool {
+        self.tracking
+            .is_some_and(|(connections, _)| connections.shutdown.is_requested())
+    }
+}
+
+/// Connection accepted by the listener
+struct OpenConnection {
+    /// Clone of the stream, used to close the connection
+    stream: TcpStream,
+    /// True while a request is being served
+    busy: bool,
+}
+
+/// Connections accepted by the listener and not closed yet, tracked to close them on shutdown
+struct OpenConnections {
+    connections: Mutex<HashMap<u64, OpenConnection>>,
+    /// Notified when a connection is removed
+    removed: Condvar,
+    next_id: AtomicU64,
+    shutdown: ShutdownHandle,
+}
+
+impl OpenConnections {
+    fn new(shutdown: ShutdownHandle) -> OpenConnections {
+        OpenConnections {
+            connections: Mutex::new(HashMap::new()),
+            removed: Condvar::new(),
+            next_id: AtomicU64::new(0),
+            shutdown,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, OpenConnection>> {
+        self.connections
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Tracks an idle connection. Returns its identifier, or `None` if it cannot be tracked.
+    fn insert(&self, stream: &TcpStream) -> Option<u64> {
+        let stream = stream.try_clone().ok()?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(
+            id,
+            OpenConnection {
+                stream,
+                busy: false,
+            },
+        );
+        Some(id)
+    }
+
+    /// Records whether a request is being served on a connection. Returns false if the
+    /// connection becomes idle while shutting down.
+    fn set_busy(&self, id: u64, busy: bool) -> bool {
+        let mut connections = self.lock();
+        // Checked with the lock held, so that the connection is either closed by `close_idle` or
+        // sees the shutdown
+        if !busy && self.shutdown.is_requested() {
+            return false;
+        }
+        if let Some(connection) = connections.get_mut(&id) {
+            connection.busy = busy;
+        }
+        true
+    }
+
+    fn remove(&self, id: u64) {
+        self.lock().remove(&id);
+        self.removed.notify_all();
+    }
+
+    /// Stops tracking a connection which cannot be served and answers it with a response.
+    /// Returns the number of body bytes sent, or `None` if the response could not be sent.
+    fn reject(&self, id: u64, response: HttpResponse) -> Option<u64> {
+        let connection = self.lock().remove(&id);
+        self.removed.notify_all();
+        let mut stream = connection?.stream;
+
+        // The listener must not be blocked by a client which does not read
+        let _ = stream.set_write_timeout(Some(REJECT_WRITE_TIMEOUT));
+        let bytes = response
+            .write_to(&mut stream)
+            .map_err(|e| debug!("Unable to reject connection: {}", e))
+            .ok();
+        // Reading the request received so far avoids resetting the connection, which could
+        // discard the response before the client reads it. Reads do not wait for more data and
+        // are bounded, so that a client which keeps sending cannot block the listener.
+        let _ = stream.shutdown(Shutdown::Write);
+        if stream.set_nonblocking(true).is_ok() {
+            let mut buffer = [0; 4096];
+            let mut drained = 0;
+            while drained < REJECT_DRAIN_SIZE {
+                match stream.read(&mut buffer) {
+                    Ok(read) if read > 0 => drained += read,
+                    _ => break,
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Closes the idle connections, then waits for the busy ones until the deadline. The
+    /// connections still open after the deadline are closed.
+    fn drain(&self, deadline: Instant) {
+        let mut connections = self.lock();
+        for connection in connections.values().filter(|connection| !connection.busy) {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+        }
+
+        while !connections.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                warn!(
+                    "Closing {} connections still busy after the shutdown timeout",
+                    connections.len()
+                );
+                for connection in connections.values() {
+                    let _ = connection.stream.shutdown(Shutdown::Both);
+                }
+                break;
+            }
+            connections = self
+                .removed
+                .wait_timeout(connections, deadline - now)
+                .unwrap_or_else(|error| error.into_inner())
+                .0;
+        }
+    }
+}
 
 /// TCP connection implementation to handle HTTP request
 pub struct TcpServerConnection {
     listener: TcpListener,
-    pool: ThreadPool,
+    config: TcpServerConfig,
 }
 
 impl TcpServerConnection {
-    /// Creates a new [`TcpServerConnection`]. Connection uses a thread pool with four threads.
+    /// Creates a new [`TcpServerConnection`] with the default configuration. Connection uses a
+    /// thread pool with a worker per available CPU.
     /// Returns std::io::Error if connection was not able to connect to provided socket.
     pub fn new(socket: SocketAddr) -> io::Result<TcpServerConnection> {
+        Self::with_config(socket, TcpServerConfig::default())
+    }
+
+    /// Creates a new [`TcpServerConnection`] using the provided configuration.
+    /// Returns std::io::Error if connection was not able to connect to provided socket.
+    pub fn with_config(
+        socket: SocketAddr,
+        config: TcpServerConfig,
+    ) -> io::Result<TcpServerConnection> {
         let listener = TcpListener::bind(socket)?;
-        Ok(TcpServerConnection {
-            listener,
-            pool: ThreadPool::new(4),
-        })
+        Ok(TcpServerConnection { listener, config })
+    }
+
+    /// Returns the address on which the connection listens, e.g. to find the port chosen by the
+    /// system when binding port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
     }
 }
 
 impl TcpServerConnection {
+    /// Serves the requests received on a stream until the connection should be closed. Requests
+    /// are handled in the order they are received, so pipelined requests get ordered responses.
     fn handle_incoming_connection<
-        Callback: Fn(&[u8]) -> Result<Vec<u8>, ServerError> + Send + Sync,
+        Callback: Fn(&HttpRequest) -> Result<HttpResponse, ServerError> + Send + Sync,
         Stream: Read + Write,
     >(
         request_handler_callback: Callback,
         stream: &mut Stream,
+        client: &Client,
+        config: &TcpServerConfig,
     ) {
-        let mut input_buffer: [u8; 1024] = [0; 1024];
-        match stream.read(&mut input_buffer) {
-            Ok(_) => {
-                match (request_handler_callback)(&input_buffer)
-                    .map(|message| stream.write(&message))
-                    .map(|_| stream.flush())
-                {
-                    Ok(_) => println!("Request was succesfully handled"),
-                    Err(e) => println!("Error when handling request: {:?}", e),
+        let mut reader = RequestReader::new(stream, config.limits.clone());
+        let mut served_requests = 0;
+
+        loop {
+            if !client.set_busy(false) {
+                break;
+            }
+            let start = Instant::now();
+            let (mut response, keep_alive, version, log_entry, span) = match reader.read_request() {
+                Ok(None) => break,
+                Ok(Some(request)) => {
+                    client.set_busy(true);
+                    served_requests += 1;
+                    // Events emitted while the request is served, including by the handler, are
+                    // recorded in this span
+                    let span = info_span!(
+                        "request",
+                        id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+                        method = %request.line.method,
+                        target = %request.line.uri,
+                    );
+                    let keep_alive = request.keep_alive() && served_requests < config.max_requests;
+                    let log_entry = config
+                        .access_log
+                        .as_ref()
+                        .map(|_| AccessLogEntry::new(&request, client.remote_addr));
+                    match span.in_scope(|| (request_handler_callback)(&request)) {
+                        Ok(response) => {
+                            (response, keep_alive, request.line.version, log_entry, span)
+                        }
+                        Err(e) => {
+                            span.in_scope(|| error!("Error when handling request: {}", e));
+                            break;
+                        }
+                    }
+                }
+                // Remaining data cannot be framed after an invalid request
+                Err(ReadError::Http(error)) => {
+                    debug!("Invalid request: {}", error);
+                    let log_entry = config
+                        .access_log
+                        .as_ref()
+                        .map(|_| AccessLogEntry::rejected(client.remote_addr, error.status_code()));
+                    (
+                        build_error_response(&error),
+                        false,
+                        reader.request_version().unwrap_or(HttpVersion::V11),
+                        log_entry,
+                        Span::none(),
+                    )
                 }
+                Err(ReadError::Io(error)) => {
+                    if matches!(
+                        error.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) {
+                        debug!("Closing idle connection");
+                    } else {
+                        warn!("Unable to read request: {}", error);
+                    }
+                    break;
+                }
+            };
+            let _entered = span.enter();
+
+            // Response is sent in the version of the request, whatever the handler built
+            *response.version_mut() = version;
+            // A body of unknown length sent to an HTTP/1.0 client ends when the connection closes
+            let keep_alive = keep_alive && response.is_delimited() && !client.shutdown_requested();
+            if !response.headers().contains_key(SERVER) {
+                response
+                    .headers_mut()
+                    .insert(SERVER, config.server_name.clone());
             }
-            Err(error) => {
-                println!("{:?}", error);
+
+            // HTTP/1.0 clients expect the server to confirm that the connection stays open
+            match (keep_alive, version) {
+                (false, _) => {
+                    response
+                        .headers_mut()
+                        .insert(CONNECTION, HeaderValue::from_static("close"));
+                }
+                (true, HttpVersion::V10) => {
+                    response
+                        .headers_mut()
+                        .insert(CONNECTION, HeaderValue::from_static("keep-alive"));
+                }
+                (true, _) => (),
+            };
+
+            let status = response.status();
+            match response.write_to_with(reader.stream_mut(), &config.write_options) {
+                Ok(bytes) => {
+                    debug!(status = status.as_u16(), bytes, "Response sent");
+                    log_access(config, log_entry, status, bytes, start);
+                }
+                Err(e) => {
+                    warn!("Unable to write response: {}", e);
+                    break;
+                }
+            }
+
+            if !keep_alive {
+                break;
             }
         }
     }
 }
 
+/// Writes the entry describing a response to the access log, if any.
+fn log_access(
+    config: &TcpServerConfig,
+    entry: Option<AccessLogEntry>,
+    status: StatusCode,
+    bytes: u64,
+    start: Instant,
+) {
+    if let (Some(access_log), Some(mut entry)) = (&config.access_log, entry) {
+        entry.status = status;
+        entry.bytes = bytes;
+        entry.duration = start.elapsed();
+        if let Err(e) = access_log.log(&entry) {
+            error!("Unable to write access log: {}", e);
+        }
+    }
+}
+
+/// Generates the response sent back to clients accepted while all the workers are busy
+fn build_unavailable_response(config: &TcpServerConfig) -> HttpResponse {
+    // Retry-After is a number of seconds
+    let retry_after =
+        config.retry_after.as_secs() + u64::from(config.retry_after.subsec_nanos() > 0);
+    HttpResponse::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header(RETRY_AFTER, HeaderValue::from(retry_after))
+        .header(CONNECTION, HeaderValue::from_static("close"))
+        .header(SERVER, config.server_name.clone())
+        .build()
+}
+
 impl Connection for TcpServerConnection {
-    /// Loop over TCP connection and handle incoming requests using the provided callback.
-    fn listen<T: 'static + Copy + Fn(&[u8]) -> Result<Vec<u8>, ServerError> + Send + Sync>(
-        &self,
-        request_handler_callback: T,
-    ) {
-        for connection in self.listener.incoming() {
-            match connection {
-                Ok(mut socket) => {
-                    self.pool.execute(move || {
-                        Self::handle_incoming_connection(&request_handler_callback, &mut socket);
+    /// Loop over TCP connection and handle incoming requests using the provided handler. Once
+    /// shutdown is requested, stops accepting clients, closes idle connections and waits up to
+    /// [`TcpServerConfig::shutdown_timeout`] for the requests being served.
+    fn listen<H: Handler + ?Sized + 'static>(&self, handler: Arc<H>, shutdown: ShutdownHandle) {
+        // The listener is polled so that a shutdown request is noticed without a new client
+        if let Err(e) = self.listener.set_nonblocking(true) {
+            error!("Unable to configure listener: {}", e);
+            return;
+        }
+        let pool = match ThreadPool::with_config(&self.config.thread_pool) {
+            Ok(pool) => pool,
+            Err(e) => {
+                error!("Unable to create worker threads: {}", e);
+                return;
+            }
+        };
+        let connections = Arc::new(OpenConnections::new(shutdown.clone()));
+
+        while !shutdown.is_requested() {
+            match self.listener.accept() {
+                Ok((mut socket, addr)) => {
+                    debug!(remote_addr = %addr, "Connection accepted");
+                    // Accepted sockets may inherit the non-blocking mode of the listener
+                    if let Err(e) = socket.set_nonblocking(false) {
+                        warn!("Unable to configure connection: {}", e);
+                        continue;
+                    }
+                    if let Err(e) = socket.set_read_timeout(self.config.keep_alive_timeout) {
+                        warn!("Unable to set keep-alive timeout: {}", e);
+                    }
+                    let id = connections.insert(&socket);
+                    let tracked = Arc::clone(&connections);
+                    let config = self.config.clone();
+                    let handler = handler.clone();
+                    let job = pool.try_execute(move || {
+                        let client = Client {
+                            remote_addr: Some(addr.ip()),
+                            tracking: id.map(|id| (tracked.as_ref(), id)),
+                        };
+                        Self::handle_incoming_connection(
+                            |request: &HttpRequest| handler.handle(request),
+                            &mut socket,
+                            &client,
+                            &config,
+                        );
+                        if let Some(id) = id {
+                            tracked.remove(id);
+                        }
                     });
+                    if let Err(job) = job {
+                        warn!(remote_addr = %addr, "All workers are busy, rejecting connection");
+                        let start = Instant::now();
+                        let status = StatusCode::SERVICE_UNAVAILABLE;
+                        let bytes = id.and_then(|id| {
+                            connections.reject(id, build_unavailable_response(&self.config))
+                        });
+                        if let Some(bytes) = bytes {
+                            let entry = AccessLogEntry::rejected(Some(addr.ip()), status);
+                            log_access(&self.config, Some(entry), status, bytes, start);
+                        }
+                        drop(job);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    shutdown.wait_timeout(ACCEPT_POLL_INTERVAL);
+                }
+                Err(e) => {
+                    warn!("Error when getting client: {}", e);
+                    shutdown.wait_timeout(ACCEPT_POLL_INTERVAL);
                 }
-                Err(e) => println!("Error when getting client: {:?}", e),
             }
         }
+
+        info!("Shutting down");
+        connections.drain(Instant::now() + self.config.shutdown_timeout);
+        // Dropping the pool waits for the workers to finish
+        drop(pool);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::http::access_log::LogFormat;
+    use crate::http::response::Body;
+    use std::sync::Mutex;
 
     struct TestStream {
         input_data: Vec<u8>,
@@ -77,10 +497,20 @@ mod tests {
         was_flushed: bool,
     }
 
+    impl TestStream {
+        fn new(input: &[u8]) -> TestStream {
+            TestStream {
+                input_data: input.to_vec(),
+                output_data: vec![],
+                was_flushed: false,
+            }
+        }
+    }
+
     impl Write for TestStream {
         fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-            self.output_data = Vec::from(buf);
-            Ok(0)
+            self.output_data.extend_from_slice(buf);
+            Ok(buf.len())
         }
 
         fn flush(&mut self) -> std::io::Result<()> {
@@ -91,47 +521,523 @@ mod tests {
 
     impl Read for TestStream {
         fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-            for (buf_byte, data) in buf.iter_mut().zip(self.input_data.iter()) {
-                *buf_byte = *data
-            }
-            Ok(0)
+            let size = buf.len().min(self.input_data.len());
+            buf[..size].copy_from_slice(&self.input_data[..size]);
+            self.input_data.drain(..size);
+            Ok(size)
         }
     }
 
+    /// Builds a response with fixed Date and Server headers, so that the output can be compared.
+    fn respond_with(body: &str) -> HttpResponse {
+        HttpResponse::builder()
+            .header(
+                http::header::DATE,
+                HeaderValue::from_static("Thu, 01 Jan 1970 00:00:00 GMT"),
+            )
+            .header(http::header::SERVER, HeaderValue::from_static("test"))
+            .body(body)
+            .build()
+    }
+
+    fn respond_with_uri(request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        Ok(respond_with(&request.line.uri))
+    }
+
+    /// Returns the expected serialization of a response built by `respond_with`.
+    fn expected(connection: Option<&str>, body: &str) -> String {
+        let connection = connection.map_or(String::new(), |option| {
+            format!("Connection: {}\r\n", option)
+        });
+        format!(
+            "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nServer: test\r\n{}Content-Length: {}\r\n\r\n{}",
+            connection,
+            body.len(),
+            body
+        )
+    }
+
     #[test]
     fn success_request_handling() {
-        let mut stream = TestStream {
-            input_data: String::from("input").as_bytes().to_vec(),
-            output_data: vec![],
-            was_flushed: false,
-        };
+        let mut stream = TestStream::new(b"GET / HTTP/1.1\r\n\r\n");
 
         TcpServerConnection::handle_incoming_connection(
-            |_| Ok(String::from("output").as_bytes().to_vec()),
+            |_| Ok(respond_with("output")),
             &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
         );
 
         assert_eq!(
-            stream.output_data,
-            String::from("output").as_bytes().to_vec()
+            String::from_utf8_lossy(&stream.output_data),
+            expected(None, "output")
         );
         assert!(stream.was_flushed,);
     }
 
     #[test]
     fn failure_request_handling() {
-        let mut stream = TestStream {
-            input_data: String::from("input").as_bytes().to_vec(),
-            output_data: vec![],
-            was_flushed: false,
-        };
+        let mut stream = TestStream::new(b"GET / HTTP/1.1\r\n\r\n");
 
         TcpServerConnection::handle_incoming_connection(
             |_| Err(ServerError::new("Test error")),
             &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
         );
 
         assert_eq!(stream.output_data, String::from("").as_bytes().to_vec());
         assert!(!stream.was_flushed,);
     }
+
+    #[test]
+    fn handle_request_larger_than_buffer() {
+        let mut input = b"GET / HTTP/1.1\r\nContent-Length: 3000\r\n\r\n".to_vec();
+        input.extend_from_slice(&[b'a'; 3000]);
+        let mut stream = TestStream::new(&input);
+
+        TcpServerConnection::handle_incoming_connection(
+            |request| {
+                assert_eq!(request.body.len(), 3000);
+                Ok(respond_with("output"))
+            },
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        assert_eq!(
+            String::from_utf8_lossy(&stream.output_data),
+            expected(None, "output")
+        );
+    }
+
+    #[test]
+    fn reject_too_large_body() {
+        let mut stream = TestStream::new(b"GET / HTTP/1.1\r\nContent-Length: 3000\r\n\r\n");
+        let config = TcpServerConfig {
+            limits: RequestLimits {
+                max_body_size: 1024,
+                ..RequestLimits::default()
+            },
+            ..TcpServerConfig::default()
+        };
+
+        TcpServerConnection::handle_incoming_connection(
+            |_| Ok(respond_with("output")),
+            &mut stream,
+            &Client::default(),
+            &config,
+        );
+
+        assert!(stream
+            .output_data
+            .starts_with(b"HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+
+    #[test]
+    fn handle_pipelined_requests_in_order() {
+        let mut stream = TestStream::new(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        assert_eq!(
+            String::from_utf8_lossy(&stream.output_data),
+            expected(None, "/a") + &expected(None, "/b")
+        );
+    }
+
+    #[test]
+    fn close_connection_when_requested() {
+        let mut stream =
+            TestStream::new(b"GET /a HTTP/1.1\r\nConnection: close\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        assert_eq!(
+            String::from_utf8_lossy(&stream.output_data),
+            expected(Some("close"), "/a")
+        );
+    }
+
+    #[test]
+    fn close_connection_after_max_requests() {
+        let mut stream = TestStream::new(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+        let config = TcpServerConfig {
+            max_requests: 1,
+            ..TcpServerConfig::default()
+        };
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client::default(),
+            &config,
+        );
+
+        assert_eq!(
+            String::from_utf8_lossy(&stream.output_data),
+            expected(Some("close"), "/a")
+        );
+    }
+
+    #[test]
+    fn close_connection_after_invalid_request() {
+        let mut stream = TestStream::new(b"GET /a HTTP/1.1\r\nBad\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        let output = String::from_utf8_lossy(&stream.output_data);
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(output.contains("\r\nConnection: close\r\n"));
+        assert!(!output.ends_with("/b"));
+    }
+
+    #[test]
+    fn answer_invalid_10_request_in_10() {
+        let mut stream = TestStream::new(b"GET /a HTTP/1.0\r\nBad\r\n\r\n");
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        let output = String::from_utf8_lossy(&stream.output_data);
+        assert!(output.starts_with("HTTP/1.0 400 Bad Request\r\n"));
+        assert!(output.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn keep_alive_for_10_client() {
+        let mut stream = TestStream::new(
+            b"GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /b HTTP/1.0\r\n\r\n",
+        );
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        assert_eq!(
+            String::from_utf8_lossy(&stream.output_data),
+            (expected(Some("keep-alive"), "/a") + &expected(Some("close"), "/b"))
+                .replace("HTTP/1.1 ", "HTTP/1.0 ")
+        );
+    }
+
+    #[test]
+    fn respond_in_request_version() {
+        let mut stream = TestStream::new(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n");
+
+        TcpServerConnection::handle_incoming_connection(
+            |_| {
+                Ok(HttpResponse::builder()
+                    .body(Body::Stream {
+                        reader: Box::new(io::Cursor::new(b"output".to_vec())),
+                        length: None,
+                    })
+                    .build())
+            },
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        let output = String::from_utf8_lossy(&stream.output_data);
+        assert!(output.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(!output.contains("Transfer-Encoding"));
+        assert!(output.contains("\r\nConnection: close\r\n"));
+        assert!(output.ends_with("\r\n\r\noutput"));
+    }
+
+    #[test]
+    fn close_connection_after_undelimited_body() {
+        let mut stream = TestStream::new(
+            b"GET /a HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /b HTTP/1.0\r\n\r\n",
+        );
+
+        TcpServerConnection::handle_incoming_connection(
+            |_| {
+                Ok(HttpResponse::builder()
+                    .version(HttpVersion::V10)
+                    .body(Body::Stream {
+                        reader: Box::new(io::Cursor::new(b"output".to_vec())),
+                        length: None,
+                    })
+                    .build())
+            },
+            &mut stream,
+            &Client::default(),
+            &TcpServerConfig::default(),
+        );
+
+        let output = String::from_utf8_lossy(&stream.output_data);
+        assert!(output.contains("\r\nConnection: close\r\n"));
+        assert!(output.ends_with("\r\n\r\noutput"));
+    }
+
+    #[test]
+    fn add_configured_server_header() {
+        let mut stream = TestStream::new(b"GET / HTTP/1.1\r\n\r\n");
+        let config = TcpServerConfig {
+            server_name: HeaderValue::from_static("custom/1.0"),
+            ..TcpServerConfig::default()
+        };
+
+        TcpServerConnection::handle_incoming_connection(
+            |_| Ok(HttpResponse::builder().body("output").build()),
+            &mut stream,
+            &Client::default(),
+            &config,
+        );
+
+        let output = String::from_utf8_lossy(&stream.output_data);
+        assert!(output.contains("\r\nServer: custom/1.0\r\n"));
+    }
+
+    /// Writer shared with the access log of a test.
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn log_served_requests() {
+        let mut stream = TestStream::new(
+            b"GET /a HTTP/1.1\r\nUser-Agent: test\r\n\r\nPOST /b HTTP/1.1\r\nReferer: http://a/\r\n\r\nGET /c HTTP/1.1\r\nBad\r\n\r\n",
+        );
+        let output = SharedWriter::default();
+        let config = TcpServerConfig {
+            access_log: Some(Arc::new(AccessLog::with_writer(
+                output.clone(),
+                LogFormat::Combined,
+            ))),
+            ..TcpServerConfig::default()
+        };
+
+        TcpServerConnection::handle_incoming_connection(
+            respond_with_uri,
+            &mut stream,
+            &Client {
+                remote_addr: Some(IpAddr::from([192, 168, 0, 1])),
+                ..Client::default()
+            },
+            &config,
+        );
+
+        let log = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("192.168.0.1 - - ["));
+        assert!(lines[0].ends_with("] \"GET /a HTTP/1.1\" 200 2 \"-\" \"test\""));
+        assert!(lines[1].ends_with("] \"POST /b HTTP/1.1\" 200 2 \"http://a/\" \"-\""));
+        assert!(lines[2].starts_with("192.168.0.1 - - ["));
+        assert!(lines[2].contains("] \"-\" 400 "));
+    }
+
+    // The subscriber comes with the dependencies of the binary
+    #[cfg(feature = "bin")]
+    #[test]
+    fn record_events_in_request_span() {
+        let mut stream = TestStream::new(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n");
+        let output = SharedWriter::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            TcpServerConnection::handle_incoming_connection(
+                |request: &HttpRequest| {
+                    tracing::info!("handling {}", request.path());
+                    respond_with_uri(request)
+                },
+                &mut stream,
+                &Client::default(),
+                &TcpServerConfig::default(),
+            );
+        });
+
+        let log = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let handled: Vec<&str> = log
+            .lines()
+            .filter(|line| line.contains("handling"))
+            .collect();
+        assert_eq!(handled.len(), 2);
+        assert!(handled[0].contains("request{id="), "{}", log);
+        assert!(handled[0].contains("method=GET target=/a}"), "{}", log);
+        assert!(handled[1].contains("target=/b}"), "{}", log);
+        assert_ne!(
+            handled[0]
+                .split("id=")
+                .nth(1)
+                .and_then(|id| id.split(' ').next()),
+            handled[1]
+                .split("id=")
+                .nth(1)
+                .and_then(|id| id.split(' ').next())
+        );
+        assert!(log.contains("Response sent status=200 bytes=2"), "{}", log);
+    }
+
+    #[test]
+    fn shutdown_gracefully() {
+        let config = TcpServerConfig {
+            thread_pool: ThreadPoolConfig {
+                workers: 2,
+                ..ThreadPoolConfig::default()
+            },
+            ..TcpServerConfig::default()
+        };
+        let connection =
+            TcpServerConnection::with_config("127.0.0.1:0".parse().unwrap(), config).unwrap();
+        let addr = connection.local_addr().unwrap();
+        let shutdown = ShutdownHandle::new();
+        let handle = shutdown.clone();
+        let server = std::thread::spawn(move || {
+            connection.listen(
+                Arc::new(|request: &HttpRequest| {
+                    if request.path() == "/slow" {
+                        std::thread::sleep(Duration::from_millis(300));
+                    }
+                    respond_with_uri(request)
+                }),
+                handle,
+            )
+        });
+
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.write_all(b"GET /fast HTTP/1.1\r\n\r\n").unwrap();
+        let mut buffer = [0; 1024];
+        assert!(idle.read(&mut buffer).unwrap() > 0);
+        let mut busy = TcpStream::connect(addr).unwrap();
+        busy.write_all(b"GET /slow HTTP/1.1\r\n\r\n").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        shutdown.shutdown();
+
+        let mut response = String::new();
+        busy.read_to_string(&mut response).unwrap();
+        assert!(response.contains("\r\nConnection: close\r\n"));
+        assert!(response.ends_with("\r\n\r\n/slow"));
+        assert_eq!(idle.read(&mut buffer).unwrap(), 0);
+        server.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn reject_clients_when_saturated() {
+        let output = SharedWriter::default();
+        let config = TcpServerConfig {
+            access_log: Some(Arc::new(AccessLog::with_writer(
+                output.clone(),
+                LogFormat::Common,
+            ))),
+            thread_pool: ThreadPoolConfig {
+                workers: 1,
+                queue_capacity: 0,
+                ..ThreadPoolConfig::default()
+            },
+            retry_after: Duration::from_millis(2500),
+            ..TcpServerConfig::default()
+        };
+        let connection =
+            TcpServerConnection::with_config("127.0.0.1:0".parse().unwrap(), config).unwrap();
+        let addr = connection.local_addr().unwrap();
+        let shutdown = ShutdownHandle::new();
+        let handle = shutdown.clone();
+        let server =
+            std::thread::spawn(move || connection.listen(Arc::new(respond_with_uri), handle));
+
+        // The only worker is kept busy by a persistent connection
+        let mut served = TcpStream::connect(addr).unwrap();
+        served.write_all(b"GET /a HTTP/1.1\r\n\r\n").unwrap();
+        let mut buffer = [0; 1024];
+        assert!(served.read(&mut buffer).unwrap() > 0);
+        let mut rejected = TcpStream::connect(addr).unwrap();
+        rejected.write_all(b"GET /b HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        rejected.read_to_string(&mut response).unwrap();
+        shutdown.shutdown();
+        server.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("\r\nRetry-After: 3\r\n"));
+        assert!(response.contains("\r\nConnection: close\r\n"));
+        let log = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(log
+            .lines()
+            .any(|line| line.starts_with("127.0.0.1 - - [") && line.ends_with("] \"-\" 503 -")));
+    }
+
+    #[test]
+    fn reject_clients_which_keep_sending() {
+        let config = TcpServerConfig {
+            thread_pool: ThreadPoolConfig {
+                workers: 1,
+                queue_capacity: 0,
+                ..ThreadPoolConfig::default()
+            },
+            ..TcpServerConfig::default()
+        };
+        let connection =
+            TcpServerConnection::with_config("127.0.0.1:0".parse().unwrap(), config).unwrap();
+        let addr = connection.local_addr().unwrap();
+        let shutdown = ShutdownHandle::new();
+        let handle = shutdown.clone();
+        let server =
+            std::thread::spawn(move || connection.listen(Arc::new(respond_with_uri), handle));
+
+        let mut served = TcpStream::connect(addr).unwrap();
+        served.write_all(b"GET /a HTTP/1.1\r\n\r\n").unwrap();
+        let mut buffer = [0; 1024];
+        assert!(served.read(&mut buffer).unwrap() > 0);
+        // The rejected client sends data until its connection is closed
+        let mut rejected = TcpStream::connect(addr).unwrap();
+        let mut sender = rejected.try_clone().unwrap();
+        let sending = std::thread::spawn(move || {
+            let data = [b'a'; 16 * 1024];
+            while sender.write_all(&data).is_ok() {}
+        });
+
+        let mut response = Vec::new();
+        rejected
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let _ = rejected.read_to_end(&mut response);
+        shutdown.shutdown();
+        server.join().unwrap();
+        sending.join().unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 503 Service Unavailable\r\n"));
+    }
 }
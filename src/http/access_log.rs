@@ -0,0 +1,473 @@
+use crate::http::request::{HttpRequest, HttpVersion};
+use http::header::{HeaderValue, REFERER, USER_AGENT};
+use http::StatusCode;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Format of the lines written to an access log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Common Log Format: `host ident user [time] "request" status bytes`
+    Common,
+    /// Common Log Format followed by the quoted `Referer` and `User-Agent` headers
+    Combined,
+    /// One JSON object per line, also containing the time spent serving the request
+    Json,
+}
+
+/// Information logged about a request once its response is sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry {
+    /// Address of the client, if known
+    pub remote_addr: Option<IpAddr>,
+    /// Time at which the request was received
+    pub time: SystemTime,
+    /// Method of the request, empty if the request was not read or could not be parsed
+    pub method: String,
+    /// Request target as received, empty if the request was not read or could not be parsed
+    pub target: String,
+    /// Version of the request
+    pub version: HttpVersion,
+    /// Status of the response
+    pub status: StatusCode,
+    /// Number of body bytes sent
+    pub bytes: u64,
+    /// Time spent between the reception of the request and the end of the response
+    pub duration: Duration,
+    /// Value of the `Referer` header of the request
+    pub referer: Option<String>,
+    /// Value of the `User-Agent` header of the request
+    pub user_agent: Option<String>,
+}
+
+impl AccessLogEntry {
+    /// Creates an entry for a request received now. The status, bytes and duration are filled
+    /// once the response is sent.
+    pub fn new(request: &HttpRequest, remote_addr: Option<IpAddr>) -> AccessLogEntry {
+        let header = |name| {
+            request
+                .headers
+                .get(name)
+                .map(|value: &HeaderValue| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        };
+        AccessLogEntry {
+            remote_addr,
+            time: SystemTime::now(),
+            method: String::from(request.line.method.as_str()),
+            target: request.line.uri.clone(),
+            version: request.line.version,
+            status: StatusCode::OK,
+            bytes: 0,
+            duration: Duration::default(),
+            referer: header(REFERER),
+            user_agent: header(USER_AGENT),
+        }
+    }
+
+    /// Creates an entry for a client answered without a request, because it could not be parsed
+    /// or because the server is busy. Its request line is logged as `-`.
+    pub fn rejected(remote_addr: Option<IpAddr>, status: StatusCode) -> AccessLogEntry {
+        AccessLogEntry {
+            remote_addr,
+            time: SystemTime::now(),
+            method: String::new(),
+            target: String::new(),
+            version: HttpVersion::V11,
+            status,
+            bytes: 0,
+            duration: Duration::default(),
+            referer: None,
+            user_agent: None,
+        }
+    }
+
+    /// Returns true if the entry describes a request which was read and parsed.
+    fn has_request_line(&self) -> bool {
+        !self.method.is_empty()
+    }
+}
+
+impl LogFormat {
+    /// Formats an entry as a line, without line terminator.
+    pub fn format(&self, entry: &AccessLogEntry) -> String {
+        match self {
+            LogFormat::Common => format_common(entry),
+            LogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                format_common(entry),
+                escape_quoted(entry.referer.as_deref().unwrap_or("-")),
+                escape_quoted(entry.user_agent.as_deref().unwrap_or("-"))
+            ),
+            LogFormat::Json => format_json(entry),
+        }
+    }
+}
+
+/// Formats an entry in Common Log Format. An empty body is logged as `-`.
+fn format_common(entry: &AccessLogEntry) -> String {
+    let bytes = match entry.bytes {
+        0 => String::from("-"),
+        bytes => bytes.to_string(),
+    };
+    let request_line = if entry.has_request_line() {
+        format!(
+            "{} {} {}",
+            escape_quoted(&entry.method),
+            escape_quoted(&entry.target),
+            entry.version
+        )
+    } else {
+        String::from("-")
+    };
+    format!(
+        "{} - - [{}] \"{}\" {} {}",
+        entry
+            .remote_addr
+            .map_or(String::from("-"), |addr| addr.to_string()),
+        common_log_time(entry.time),
+        request_line,
+        entry.status.as_u16(),
+        bytes
+    )
+}
+
+/// Formats an entry as a JSON object.
+fn format_json(entry: &AccessLogEntry) -> String {
+    let string_or_null = |value: Option<&str>| value.map_or(String::from("null"), json_string);
+    // Fields of the request line are null if there is no request line
+    let request_field =
+        |value: &str| string_or_null(Some(value).filter(|_| entry.has_request_line()));
+    format!(
+        "{{\"time\":{},\"remote_addr\":{},\"method\":{},\"target\":{},\"protocol\":{},\"status\":{},\"bytes\":{},\"duration_ms\":{:.3},\"referer\":{},\"user_agent\":{}}}",
+        json_string(&rfc3339_time(entry.time)),
+        string_or_null(entry.remote_addr.map(|addr| addr.to_string()).as_deref()),
+        request_field(&entry.method),
+        request_field(&entry.target),
+        request_field(entry.version.as_str()),
+        entry.status.as_u16(),
+        entry.bytes,
+        entry.duration.as_secs_f64() * 1000.0,
+        string_or_null(entry.referer.as_deref()),
+        string_or_null(entry.user_agent.as_deref())
+    )
+}
+
+/// Escapes a value written between quotes in Common Log Format. Quotes, backslashes and bytes
+/// which are not printable ASCII are escaped, so that a client cannot forge log lines.
+fn escape_quoted(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Returns a value as a JSON string, including its quotes.
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Formats a time as in Common Log Format, e.g. `10/Oct/2000:13:55:36 +0000`.
+fn common_log_time(time: SystemTime) -> String {
+    // The HTTP date has the same fields, e.g. `Tue, 10 Oct 2000 13:55:36 GMT`
+    let date = httpdate::fmt_http_date(time);
+    let fields: Vec<&str> = date.split(' ').collect();
+    format!(
+        "{}/{}/{}:{} +0000",
+        fields[1], fields[2], fields[3], fields[4]
+    )
+}
+
+/// Formats a time as in RFC 3339 with milliseconds, e.g. `2000-10-10T13:55:36.000Z`.
+fn rfc3339_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Converts a number of days since 1970-01-01 to a date of the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Days are counted from 0000-03-01, so that leap days end the 400 years eras
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u32;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Destination of the lines of an access log
+enum Output {
+    Stdout,
+    File { path: PathBuf, file: File },
+    Writer(Box<dyn Write + Send>),
+}
+
+/// Opens a log file for appending, creating it if needed.
+fn open_log_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Access log written once per request, to the standard output or to a file.
+///
+/// Log files can be reopened with [`AccessLog::reopen`], or on `SIGHUP` once
+/// [`AccessLog::reopen_on_sighup`] is called, so that they can be rotated by tools like logrotate.
+pub struct AccessLog {
+    format: LogFormat,
+    output: Mutex<Output>,
+    /// Set when the log file should be reopened before the next line is written
+    reopen_requested: Arc<AtomicBool>,
+    /// Signal handlers setting `reopen_requested`, removed when the log is dropped
+    #[cfg(unix)]
+    signal_ids: Mutex<Vec<signal_hook::SigId>>,
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let output = match &*self
+            .output
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+        {
+            Output::Stdout => String::from("stdout"),
+            Output::File { path, .. } => path.display().to_string(),
+            Output::Writer(_) => String::from("writer"),
+        };
+        f.debug_struct("AccessLog")
+            .field("format", &self.format)
+            .field("output", &output)
+            .finish()
+    }
+}
+
+impl AccessLog {
+    fn new(output: Output, format: LogFormat) -> AccessLog {
+        AccessLog {
+            format,
+            output: Mutex::new(output),
+            reopen_requested: Arc::new(AtomicBool::new(false)),
+            #[cfg(unix)]
+            signal_ids: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates a new [`AccessLog`] written to the standard output.
+    pub fn stdout(format: LogFormat) -> AccessLog {
+        AccessLog::new(Output::Stdout, format)
+    }
+
+    /// Creates a new [`AccessLog`] appended to a file, which is created if needed.
+    pub fn file<P: Into<PathBuf>>(path: P, format: LogFormat) -> io::Result<AccessLog> {
+        let path = path.into();
+        let file = open_log_file(&path)?;
+        Ok(AccessLog::new(Output::File { path, file }, format))
+    }
+
+    /// Creates a new [`AccessLog`] written to any writer.
+    pub fn with_writer<W: Write + Send + 'static>(writer: W, format: LogFormat) -> AccessLog {
+        AccessLog::new(Output::Writer(Box::new(writer)), format)
+    }
+
+    /// Returns the format of the lines of the log.
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    /// Reopens the log file before the next line is written, e.g. after it was moved.
+    pub fn reopen(&self) {
+        self.reopen_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Reopens the log file before the next line is written each time the process receives
+    /// `SIGHUP`. The signal handler is removed when the log is dropped.
+    #[cfg(unix)]
+    pub fn reopen_on_sighup(&self) -> io::Result<()> {
+        let id = signal_hook::flag::register(
+            signal_hook::consts::SIGHUP,
+            Arc::clone(&self.reopen_requested),
+        )?;
+        self.signal_ids
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .push(id);
+        Ok(())
+    }
+
+    /// Writes a line describing a served request.
+    pub fn log(&self, entry: &AccessLogEntry) -> io::Result<()> {
+        let mut line = self.format.format(entry);
+        line.push('\n');
+
+        let mut output = self
+            .output
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if let Output::File { path, file } = &mut *output {
+            if self.reopen_requested.swap(false, Ordering::Relaxed) {
+                *file = open_log_file(path)?;
+            }
+        }
+        match &mut *output {
+            Output::Stdout => io::stdout().lock().write_all(line.as_bytes()),
+            Output::File { file, .. } => file.write_all(line.as_bytes()),
+            Output::Writer(writer) => writer.write_all(line.as_bytes()),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for AccessLog {
+    fn drop(&mut self) {
+        let ids = self
+            .signal_ids
+            .get_mut()
+            .unwrap_or_else(|error| error.into_inner());
+        for id in ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_test_dir;
+    use std::fs;
+    use std::str::FromStr;
+
+    fn entry() -> AccessLogEntry {
+        let request = HttpRequest::from_str(
+            "GET /a%20b?q=\"x\" HTTP/1.1\r\nUser-Agent: curl/8.0\r\nReferer: http://example.com/\r\n\r\n",
+        )
+        .unwrap();
+        AccessLogEntry {
+            time: UNIX_EPOCH + Duration::from_millis(971_186_136_250),
+            status: StatusCode::NOT_FOUND,
+            bytes: 1234,
+            duration: Duration::from_micros(1500),
+            ..AccessLogEntry::new(&request, Some(IpAddr::from([127, 0, 0, 1])))
+        }
+    }
+
+    #[test]
+    fn format_common_and_combined() {
+        let mut entry = entry();
+
+        assert_eq!(
+            LogFormat::Combined.format(&entry),
+            "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /a%20b?q=\\\"x\\\" HTTP/1.1\" 404 1234 \"http://example.com/\" \"curl/8.0\""
+        );
+        entry.bytes = 0;
+        entry.remote_addr = None;
+        entry.user_agent = Some(String::from("evil\"\n"));
+        assert_eq!(
+            LogFormat::Common.format(&entry),
+            "- - - [10/Oct/2000:13:55:36 +0000] \"GET /a%20b?q=\\\"x\\\" HTTP/1.1\" 404 -"
+        );
+        assert!(LogFormat::Combined
+            .format(&entry)
+            .ends_with(" \"evil\\\"\\x0a\""));
+    }
+
+    #[test]
+    fn format_json() {
+        let mut entry = entry();
+        entry.referer = None;
+
+        assert_eq!(
+            LogFormat::Json.format(&entry),
+            "{\"time\":\"2000-10-10T13:55:36.250Z\",\"remote_addr\":\"127.0.0.1\",\"method\":\"GET\",\"target\":\"/a%20b?q=\\\"x\\\"\",\"protocol\":\"HTTP/1.1\",\"status\":404,\"bytes\":1234,\"duration_ms\":1.500,\"referer\":null,\"user_agent\":\"curl/8.0\"}"
+        );
+    }
+
+    #[test]
+    fn format_rejected_client() {
+        let entry = AccessLogEntry {
+            time: UNIX_EPOCH + Duration::from_millis(971_186_136_250),
+            ..AccessLogEntry::rejected(
+                Some(IpAddr::from([127, 0, 0, 1])),
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+        };
+
+        assert_eq!(
+            LogFormat::Combined.format(&entry),
+            "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"-\" 503 - \"-\" \"-\""
+        );
+        assert!(LogFormat::Json
+            .format(&entry)
+            .contains("\"method\":null,\"target\":null,\"protocol\":null,\"status\":503"));
+    }
+
+    #[test]
+    fn convert_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_783), (2024, 3, 1));
+        assert_eq!(rfc3339_time(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn reopen_moved_file() {
+        let dir = empty_test_dir("access-log");
+        let path = dir.join("access.log");
+        let log = AccessLog::file(&path, LogFormat::Common).expect("");
+        #[cfg(unix)]
+        log.reopen_on_sighup().expect("");
+
+        log.log(&entry()).expect("");
+        fs::rename(&path, dir.join("access.log.1")).expect("");
+        log.log(&entry()).expect("");
+        #[cfg(unix)]
+        signal_hook::low_level::raise(signal_hook::consts::SIGHUP).expect("");
+        #[cfg(not(unix))]
+        log.reopen();
+        log.log(&entry()).expect("");
+
+        let rotated = fs::read_to_string(dir.join("access.log.1")).expect("");
+        let current = fs::read_to_string(&path).expect("");
+        assert_eq!(rotated.lines().count(), 2);
+        assert_eq!(current.lines().count(), 1);
+    }
+}
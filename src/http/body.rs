@@ -0,0 +1,516 @@
+use crate::http::parser::{parse_header_field, trim_whitespace};
+use crate::http::reader::RequestLimits;
+use crate::http::request::{HttpRequestError, HttpRequestErrorKind};
+use http::header::{HeaderMap, CONTENT_LENGTH, TRANSFER_ENCODING};
+
+/// Maximum length of the line giving the size of a chunk, including its extensions
+const MAX_CHUNK_LINE_LENGTH: usize = 1024;
+
+/// How the length of a request body is determined, as described in RFC 9112 section 6.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    /// Request has no body
+    Empty,
+    /// Body length in bytes is given by the Content-Length header
+    Length(usize),
+    /// Body is sent using the chunked transfer coding
+    Chunked,
+}
+
+impl BodyFraming {
+    /// Finds the framing of a request body from the request headers.
+    /// Returns [`HttpRequestError`] if the framing headers are invalid or ambiguous.
+    pub fn from_headers(headers: &HeaderMap) -> Result<BodyFraming, HttpRequestError> {
+        if headers.contains_key(TRANSFER_ENCODING) {
+            // A message with both headers could be interpreted differently by intermediaries,
+            // which opens the door to request smuggling
+            if headers.contains_key(CONTENT_LENGTH) {
+                return Err(HttpRequestError::new(
+                    HttpRequestErrorKind::InvalidBody,
+                    "Both Transfer-Encoding and Content-Length are present",
+                ));
+            }
+
+            let mut codings = Vec::new();
+            for value in headers.get_all(TRANSFER_ENCODING) {
+                let value = value.to_str().map_err(|_| {
+                    HttpRequestError::new(
+                        HttpRequestErrorKind::InvalidBody,
+                        "Transfer-Encoding is not valid ascii",
+                    )
+                })?;
+                codings.extend(
+                    value
+                        .split(',')
+                        .map(|coding| coding.trim().to_ascii_lowercase())
+                        .filter(|coding| !coding.is_empty()),
+                );
+            }
+
+            return match codings.as_slice() {
+                [coding] if coding == "chunked" => Ok(BodyFraming::Chunked),
+                _ => Err(HttpRequestError::new(
+                    HttpRequestErrorKind::UnsupportedTransferCoding,
+                    "Only the chunked transfer coding is supported",
+                )),
+            };
+        }
+
+        let mut length = None;
+        for value in headers.get_all(CONTENT_LENGTH) {
+            for item in value.as_bytes().split(|&byte| byte == b',') {
+                let item = parse_content_length(item)?;
+                if length.is_some_and(|length| length != item) {
+                    return Err(HttpRequestError::new(
+                        HttpRequestErrorKind::InvalidBody,
+                        "Content-Length values differ",
+                    ));
+                }
+                length = Some(item);
+            }
+        }
+
+        Ok(match length {
+            Some(0) | None => BodyFraming::Empty,
+            Some(length) => BodyFraming::Length(length),
+        })
+    }
+}
+
+/// Parses a single Content-Length value, which must only contain digits.
+fn parse_content_length(value: &[u8]) -> Result<usize, HttpRequestError> {
+    let value = trim_whitespace(value);
+    let error = || {
+        HttpRequestError::new(
+            HttpRequestErrorKind::InvalidBody,
+            "Content-Length is not a valid length",
+        )
+    };
+
+    if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+        return Err(error());
+    }
+
+    std::str::from_utf8(value)
+        .map_err(|_| error())?
+        .parse()
+        .map_err(|_| error())
+}
+
+/// Request body decoded from its transfer coding
+#[derive(Debug, Default)]
+pub struct DecodedBody {
+    /// Content of the body
+    pub data: Vec<u8>,
+    /// Trailer fields sent after the last chunk of a chunked body
+    pub trailers: HeaderMap,
+    /// Number of input bytes used by the body, including the chunked coding if any
+    pub length: usize,
+}
+
+/// Decodes a body following the provided framing from the start of `data`.
+/// Returns `None` if `data` does not contain the complete body yet. Returns [`HttpRequestError`]
+/// if the body is malformed or if its decoded size exceeds `max_size`. Trailers are limited as
+/// with the default [`RequestLimits`].
+pub fn decode_body(
+    framing: BodyFraming,
+    data: &[u8],
+    max_size: usize,
+) -> Result<Option<DecodedBody>, HttpRequestError> {
+    let limits = RequestLimits {
+        max_body_size: max_size,
+        ..RequestLimits::default()
+    };
+    BodyDecoder::new(framing, &limits).decode(data)
+}
+
+/// Part of a chunked body expected next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkedState {
+    /// Line giving the size of the next chunk
+    Size,
+    /// Number of bytes of the current chunk not received yet
+    Data(usize),
+    /// Line terminator following the data of a chunk
+    DataEnd,
+    /// Trailer fields, ended by an empty line
+    Trailers,
+}
+
+/// Decodes a body received in several parts.
+/// Each call to [`BodyDecoder::decode`] resumes where the previous one stopped, so that a body
+/// received in many small reads is decoded only once.
+#[derive(Debug)]
+pub struct BodyDecoder {
+    framing: BodyFraming,
+    limits: RequestLimits,
+    /// Content, trailers and input length decoded so far
+    body: DecodedBody,
+    state: ChunkedState,
+    /// Position up to which the current line was searched for its terminator
+    searched: usize,
+    /// Size in bytes of the trailer fields received so far
+    trailers_size: usize,
+}
+
+impl BodyDecoder {
+    /// Creates a new [`BodyDecoder`] for a body following the provided framing. Bodies larger
+    /// than `max_body_size` are rejected, and trailer sections are limited like request heads.
+    pub fn new(framing: BodyFraming, limits: &RequestLimits) -> BodyDecoder {
+        BodyDecoder {
+            framing,
+            limits: limits.clone(),
+            body: DecodedBody::default(),
+            state: ChunkedState::Size,
+            searched: 0,
+            trailers_size: 0,
+        }
+    }
+
+    /// Decodes the body at the start of `data`, which must start with the data passed to the
+    /// previous calls. Returns `None` if `data` does not contain the complete body yet. Returns
+    /// [`HttpRequestError`] if the body is malformed or too large.
+    pub fn decode(&mut self, data: &[u8]) -> Result<Option<DecodedBody>, HttpRequestError> {
+        match self.framing {
+            BodyFraming::Empty => Ok(Some(DecodedBody::default())),
+            BodyFraming::Length(length) => {
+                if length > self.limits.max_body_size {
+                    return Err(body_too_large());
+                }
+                if data.len() < length {
+                    return Ok(None);
+                }
+                Ok(Some(DecodedBody {
+                    data: data[..length].to_vec(),
+                    trailers: HeaderMap::new(),
+                    length,
+                }))
+            }
+            BodyFraming::Chunked => self.decode_chunked(data),
+        }
+    }
+
+    /// Decodes a body sent with the chunked transfer coding, including its trailer section.
+    fn decode_chunked(&mut self, data: &[u8]) -> Result<Option<DecodedBody>, HttpRequestError> {
+        loop {
+            if let ChunkedState::Data(remaining) = self.state {
+                let position = self.body.length;
+                let available = remaining.min(data.len() - position);
+                self.body
+                    .data
+                    .extend_from_slice(&data[position..position + available]);
+                self.body.length += available;
+                if available < remaining {
+                    self.state = ChunkedState::Data(remaining - available);
+                    return Ok(None);
+                }
+                self.state = ChunkedState::DataEnd;
+                continue;
+            }
+
+            let line = match self.next_line(data) {
+                Some(line) => line,
+                None => {
+                    // Line is not complete, but its received part may already be too long
+                    self.check_line_length(data.len() - self.body.length)?;
+                    return Ok(None);
+                }
+            };
+            self.check_line_length(line.len())?;
+            match self.state {
+                ChunkedState::Size => {
+                    let size = parse_chunk_size(line)?;
+                    if size
+                        > self
+                            .limits
+                            .max_body_size
+                            .saturating_sub(self.body.data.len())
+                    {
+                        return Err(body_too_large());
+                    }
+                    self.state = match size {
+                        0 => ChunkedState::Trailers,
+                        size => ChunkedState::Data(size),
+                    };
+                }
+                ChunkedState::DataEnd if line.is_empty() => self.state = ChunkedState::Size,
+                ChunkedState::DataEnd => {
+                    return Err(malformed_chunk("Chunk data is not followed by CRLF"))
+                }
+                ChunkedState::Trailers if line.is_empty() => {
+                    return Ok(Some(std::mem::take(&mut self.body)))
+                }
+                ChunkedState::Trailers => {
+                    if self.body.trailers.len() >= self.limits.max_headers {
+                        return Err(HttpRequestError::new(
+                            HttpRequestErrorKind::TooManyHeaders,
+                            "Request contains too many trailer fields",
+                        ));
+                    }
+                    self.trailers_size += line.len();
+                    let (name, value) = parse_header_field(line)?.to_header()?;
+                    self.body.trailers.append(name, value);
+                }
+                ChunkedState::Data(_) => unreachable!("Chunk data is not a line"),
+            }
+        }
+    }
+
+    /// Checks the length of the line at the current position: chunk size lines are limited to a
+    /// fixed length and the trailer section to the maximum size of a request head.
+    fn check_line_length(&self, length: usize) -> Result<(), HttpRequestError> {
+        match self.state {
+            ChunkedState::Trailers if self.trailers_size + length > self.limits.max_header_size => {
+                Err(HttpRequestError::new(
+                    HttpRequestErrorKind::HeadersTooLarge,
+                    "Trailer section exceeds the maximum allowed size",
+                ))
+            }
+            ChunkedState::Trailers => Ok(()),
+            _ if length > MAX_CHUNK_LINE_LENGTH => Err(malformed_chunk("Chunk line is too long")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the line starting at the current position without its line terminator, and moves
+    /// the position to the next line. Lines may end with CRLF or a bare LF. Returns `None` if the
+    /// line is not complete, remembering how far it was searched.
+    fn next_line<'a>(&mut self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.body.length;
+        let search_start = self.searched.max(start);
+        let end = match data[search_start..].iter().position(|&byte| byte == b'\n') {
+            Some(offset) => search_start + offset,
+            None => {
+                self.searched = data.len();
+                return None;
+            }
+        };
+
+        self.body.length = end + 1;
+        self.searched = end + 1;
+        let line = &data[start..end];
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+}
+
+fn body_too_large() -> HttpRequestError {
+    HttpRequestError::new(
+        HttpRequestErrorKind::BodyTooLarge,
+        "Request body exceeds the maximum allowed size",
+    )
+}
+
+fn malformed_chunk(msg: &str) -> HttpRequestError {
+    HttpRequestError::new(HttpRequestErrorKind::InvalidBody, msg)
+}
+
+/// Parses the hexadecimal size at the start of a chunk-size line, ignoring chunk extensions.
+fn parse_chunk_size(line: &[u8]) -> Result<usize, HttpRequestError> {
+    let digits = line
+        .iter()
+        .position(|byte| !byte.is_ascii_hexdigit())
+        .map_or(line, |end| &line[..end]);
+    let rest = trim_whitespace(&line[digits.len()..]);
+
+    if digits.is_empty() || !(rest.is_empty() || rest.starts_with(b";")) {
+        return Err(malformed_chunk(
+            "Chunk size is not a valid hexadecimal number",
+        ));
+    }
+
+    digits.iter().try_fold(0usize, |size, &digit| {
+        let value = (digit as char).to_digit(16).unwrap_or(0) as usize;
+        size.checked_mul(16)
+            .and_then(|size| size.checked_add(value))
+            .ok_or_else(|| malformed_chunk("Chunk size is too large"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::request::HttpRequest;
+    use http::header::HeaderValue;
+    use http::StatusCode;
+    use std::str::FromStr;
+
+    fn limits(max_body_size: usize) -> RequestLimits {
+        RequestLimits {
+            max_body_size,
+            ..RequestLimits::default()
+        }
+    }
+
+    fn headers(fields: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn framing_without_body_headers() {
+        let result = BodyFraming::from_headers(&HeaderMap::new());
+
+        assert!(matches!(result, Ok(BodyFraming::Empty)));
+    }
+
+    #[test]
+    fn framing_with_content_length() {
+        let result = BodyFraming::from_headers(&headers(&[("content-length", "42")]));
+
+        assert!(matches!(result, Ok(BodyFraming::Length(42))));
+    }
+
+    #[test]
+    fn framing_with_invalid_content_length() {
+        let result = BodyFraming::from_headers(&headers(&[("content-length", "+42")]));
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidBody));
+    }
+
+    #[test]
+    fn framing_with_conflicting_content_lengths() {
+        let result = BodyFraming::from_headers(&headers(&[
+            ("content-length", "42"),
+            ("content-length", "43"),
+        ]));
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidBody));
+    }
+
+    #[test]
+    fn framing_with_chunked_and_content_length() {
+        let result = BodyFraming::from_headers(&headers(&[
+            ("transfer-encoding", "chunked"),
+            ("content-length", "42"),
+        ]));
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidBody));
+    }
+
+    #[test]
+    fn framing_with_unsupported_coding() {
+        let result = BodyFraming::from_headers(&headers(&[("transfer-encoding", "gzip, chunked")]));
+
+        assert!(matches!(
+            result,
+            Err(e) if e.kind() == HttpRequestErrorKind::UnsupportedTransferCoding
+        ));
+    }
+
+    #[test]
+    fn decode_incomplete_length_body() {
+        let result = decode_body(BodyFraming::Length(10), b"hello", 1024);
+
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[test]
+    fn decode_too_large_length_body() {
+        let result = decode_body(BodyFraming::Length(10), b"hello", 5);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::BodyTooLarge));
+    }
+
+    #[test]
+    fn decode_chunked_body_with_trailers() {
+        let data = b"5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\nExpires: never\r\n\r\nnext";
+
+        let result = decode_body(BodyFraming::Chunked, data, 1024)
+            .expect("")
+            .expect("");
+
+        assert_eq!(result.data, b"hello, world".to_vec());
+        assert_eq!(result.trailers["expires"], "never");
+        assert_eq!(result.length, data.len() - 4);
+    }
+
+    #[test]
+    fn decode_chunked_body_in_parts() {
+        let data = b"5\r\nhello\r\n7;a=b\r\n, world\r\n0\r\nExpires: never\r\n\r\n";
+        let mut decoder = BodyDecoder::new(BodyFraming::Chunked, &limits(1024));
+
+        for end in 0..data.len() {
+            assert!(matches!(decoder.decode(&data[..end]), Ok(None)));
+        }
+        let result = decoder.decode(data).expect("").expect("");
+
+        assert_eq!(result.data, b"hello, world".to_vec());
+        assert_eq!(result.trailers["expires"], "never");
+        assert_eq!(result.length, data.len());
+    }
+
+    #[test]
+    fn reject_endless_trailer_section() {
+        let mut data = b"5\r\nhello\r\n0\r\n".to_vec();
+        let mut decoder = BodyDecoder::new(BodyFraming::Chunked, &limits(1024));
+
+        let result = loop {
+            data.extend_from_slice(b"X-Trailer: value\r\n");
+            match decoder.decode(&data) {
+                Ok(None) => continue,
+                result => break result,
+            }
+        };
+
+        assert!(
+            matches!(result, Err(e) if e.status_code() == StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+        assert!(data.len() < 2 * RequestLimits::default().max_header_size);
+    }
+
+    #[test]
+    fn reject_endless_chunk_extension() {
+        let mut data = b"5;ext=".to_vec();
+        let mut decoder = BodyDecoder::new(BodyFraming::Chunked, &limits(1024));
+
+        let result = loop {
+            data.extend_from_slice(b"aaaaaaaa");
+            match decoder.decode(&data) {
+                Ok(None) => continue,
+                result => break result,
+            }
+        };
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidBody));
+        assert!(data.len() <= MAX_CHUNK_LINE_LENGTH + 8);
+    }
+
+    #[test]
+    fn decode_incomplete_chunked_body() {
+        let result = decode_body(BodyFraming::Chunked, b"5\r\nhello\r\n0\r\n", 1024);
+
+        assert!(matches!(result, Ok(None)));
+    }
+
+    #[test]
+    fn decode_malformed_chunked_body() {
+        let result = decode_body(BodyFraming::Chunked, b"5\r\nhello world\r\n0\r\n\r\n", 1024);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidBody));
+    }
+
+    #[test]
+    fn decode_too_large_chunked_body() {
+        let result = decode_body(BodyFraming::Chunked, b"5\r\nhello\r\n5\r\nworld\r\n", 8);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::BodyTooLarge));
+    }
+
+    #[test]
+    fn decode_chunked_body_with_huge_chunk_size() {
+        let result = decode_body(
+            BodyFraming::Chunked,
+            b"ffffffffffffffff\r\nab\r\n0\r\n\r\n",
+            usize::MAX,
+        );
+
+        assert!(matches!(result, Ok(None)));
+        assert!(HttpRequest::from_str(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\nab\r\n0\r\n\r\n"
+        )
+        .is_err());
+    }
+}
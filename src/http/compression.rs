@@ -0,0 +1,276 @@
+use crate::http::response::Body;
+use flate2::read::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use http::header::HeaderValue;
+use mime::Mime;
+use std::io::Read;
+
+/// Quality used by brotli for on-the-fly compression. Higher qualities are too slow to be used
+/// for every request.
+const BROTLI_QUALITY: u32 = 5;
+/// Base two logarithm of the window size used by brotli
+const BROTLI_WINDOW: u32 = 22;
+/// Size of the buffer used by brotli
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Content coding of a response body, as described in RFC 9110 section 8.4.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+    Identity,
+}
+
+/// Encodings supported by the server, in order of preference when the client accepts several of
+/// them with the same weight
+const SUPPORTED_ENCODINGS: [Encoding; 4] = [
+    Encoding::Brotli,
+    Encoding::Gzip,
+    Encoding::Deflate,
+    Encoding::Identity,
+];
+
+impl Encoding {
+    /// Returns the name of the encoding used in `Accept-Encoding` and `Content-Encoding`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Identity => "identity",
+        }
+    }
+
+    /// Returns the extension of files precompressed with this encoding, e.g. `gz` for
+    /// `page.html.gz`.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Brotli => Some("br"),
+            Encoding::Gzip => Some("gz"),
+            Encoding::Deflate | Encoding::Identity => None,
+        }
+    }
+
+    /// Returns the value of the `Content-Encoding` header for this encoding.
+    pub fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_static(self.as_str())
+    }
+
+    /// Wraps a reader so that it produces its content compressed with this encoding.
+    pub fn encode<R: Read + Send + 'static>(&self, reader: R) -> Body {
+        let reader: Box<dyn Read + Send> = match self {
+            Encoding::Brotli => Box::new(brotli::CompressorReader::new(
+                reader,
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW,
+            )),
+            Encoding::Gzip => Box::new(GzEncoder::new(reader, Compression::default())),
+            // The deflate content coding is the zlib format (RFC 9110 section 8.4.1.2)
+            Encoding::Deflate => Box::new(ZlibEncoder::new(reader, Compression::default())),
+            Encoding::Identity => Box::new(reader),
+        };
+        Body::Stream {
+            reader,
+            length: None,
+        }
+    }
+}
+
+/// Parses an `Accept-Encoding` header into codings with their weight. Codings with an invalid
+/// weight are ignored.
+fn parse_accept_encoding(value: &str) -> Vec<(String, f32)> {
+    value
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';').map(str::trim);
+            let coding = params.next().filter(|coding| !coding.is_empty())?;
+            let mut weight = 1.0;
+            for param in params {
+                if let Some((name, value)) = param.split_once('=') {
+                    if name.trim().eq_ignore_ascii_case("q") {
+                        weight = value.trim().parse::<f32>().ok()?;
+                    }
+                }
+            }
+            if !(0.0..=1.0).contains(&weight) {
+                return None;
+            }
+            Some((coding.to_ascii_lowercase(), weight))
+        })
+        .collect()
+}
+
+/// Returns the encodings accepted by the client, from the most to the least preferred, following
+/// RFC 9110 section 12.5.3. Without header, only the identity is accepted. The identity is
+/// accepted unless it is explicitly refused.
+pub fn accepted_encodings(accept_encoding: Option<&HeaderValue>) -> Vec<Encoding> {
+    let codings = match accept_encoding.map(HeaderValue::to_str) {
+        Some(Ok(value)) => parse_accept_encoding(value),
+        Some(Err(_)) | None => return vec![Encoding::Identity],
+    };
+    let weight_of = |name: &str| {
+        codings
+            .iter()
+            .find(|(coding, _)| coding == name)
+            .or_else(|| codings.iter().find(|(coding, _)| coding == "*"))
+            .map(|(_, weight)| *weight)
+    };
+
+    let mut encodings: Vec<(Encoding, f32)> = SUPPORTED_ENCODINGS
+        .iter()
+        .filter_map(|encoding| {
+            let weight = match (encoding, weight_of(encoding.as_str())) {
+                // "x-gzip" is an alias of "gzip" (RFC 9110 section 8.4.1.3)
+                (Encoding::Gzip, None) => weight_of("x-gzip"),
+                (Encoding::Identity, None) => Some(0.001),
+                (_, weight) => weight,
+            }?;
+            (weight > 0.0).then_some((*encoding, weight))
+        })
+        .collect();
+    // Stable sort keeps the order of preference of the server for equal weights
+    encodings.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    encodings
+        .into_iter()
+        .map(|(encoding, _)| encoding)
+        .collect()
+}
+
+/// Returns true if content of this type is worth compressing. Most image, audio and video
+/// formats are already compressed.
+pub fn is_compressible(mime: &Mime) -> bool {
+    let subtype = mime.subtype().as_str();
+    match mime.type_().as_str() {
+        "text" => true,
+        "application" => {
+            matches!(
+                subtype,
+                "javascript"
+                    | "json"
+                    | "xml"
+                    | "wasm"
+                    | "yaml"
+                    | "toml"
+                    | "rtf"
+                    | "x-sh"
+                    | "xml-dtd"
+                    | "postscript"
+                    | "vnd.ms-fontobject"
+            ) || matches!(
+                mime.suffix().map(|suffix| suffix.as_str()),
+                Some("json" | "xml")
+            )
+        }
+        "image" => matches!(subtype, "svg" | "bmp" | "x-icon" | "vnd.microsoft.icon"),
+        "font" => matches!(subtype, "ttf" | "otf"),
+        _ => false,
+    }
+}
+
+/// Compression settings of a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Compresses responses while they are sent
+    pub on_the_fly: bool,
+    /// Smallest file, in bytes, compressed on the fly. Compressing small files saves few bytes
+    /// and costs time.
+    pub min_size: u64,
+    /// Serves `file.br` or `file.gz`, if present, instead of compressing `file` on the fly
+    pub precompressed: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            on_the_fly: true,
+            min_size: 1024,
+            precompressed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepted(value: &'static str) -> Vec<Encoding> {
+        accepted_encodings(Some(&HeaderValue::from_static(value)))
+    }
+
+    fn decode(body: Body, encoding: Encoding) -> Vec<u8> {
+        let reader = match body {
+            Body::Stream { reader, .. } => reader,
+            other => panic!("Unexpected body {:?}", other),
+        };
+        let mut decoded = Vec::new();
+        match encoding {
+            Encoding::Brotli => brotli::Decompressor::new(reader, 4096).read_to_end(&mut decoded),
+            Encoding::Gzip => flate2::read::GzDecoder::new(reader).read_to_end(&mut decoded),
+            Encoding::Deflate => flate2::read::ZlibDecoder::new(reader).read_to_end(&mut decoded),
+            Encoding::Identity => { reader }.read_to_end(&mut decoded),
+        }
+        .expect("");
+        decoded
+    }
+
+    #[test]
+    fn negotiate_with_weights() {
+        assert_eq!(
+            accepted("gzip;q=0.5, br;q=0.8, deflate;q=0.1"),
+            vec![
+                Encoding::Brotli,
+                Encoding::Gzip,
+                Encoding::Deflate,
+                Encoding::Identity
+            ]
+        );
+        assert_eq!(
+            accepted("deflate, gzip"),
+            vec![Encoding::Gzip, Encoding::Deflate, Encoding::Identity]
+        );
+        assert_eq!(accepted("x-gzip"), vec![Encoding::Gzip, Encoding::Identity]);
+        assert_eq!(accepted_encodings(None), vec![Encoding::Identity]);
+        assert_eq!(accepted(""), vec![Encoding::Identity]);
+    }
+
+    #[test]
+    fn negotiate_with_wildcard_and_refusals() {
+        assert_eq!(
+            accepted("*;q=0.5, gzip;q=0, identity;q=0.9"),
+            vec![Encoding::Identity, Encoding::Brotli, Encoding::Deflate]
+        );
+        assert_eq!(accepted("br, identity;q=0"), vec![Encoding::Brotli]);
+        assert_eq!(accepted("*;q=0"), Vec::<Encoding>::new());
+        assert_eq!(accepted("gzip;q=2, br;q=abc"), vec![Encoding::Identity]);
+    }
+
+    #[test]
+    fn find_compressible_types() {
+        assert!(is_compressible(&mime::TEXT_HTML_UTF_8));
+        assert!(is_compressible(&mime::APPLICATION_JSON));
+        assert!(is_compressible(&mime::IMAGE_SVG));
+        assert!(is_compressible(&"application/ld+json".parse().expect("")));
+        assert!(!is_compressible(&mime::IMAGE_PNG));
+        assert!(!is_compressible(&"application/gzip".parse().expect("")));
+        assert!(!is_compressible(&mime::FONT_WOFF2));
+    }
+
+    #[test]
+    fn encode_and_decode_content() {
+        let content = "hello world ".repeat(100).into_bytes();
+
+        for encoding in SUPPORTED_ENCODINGS {
+            let body = encoding.encode(std::io::Cursor::new(content.clone()));
+            assert_eq!(decode(body, encoding), content, "{:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn encoding_names() {
+        assert_eq!(Encoding::Gzip.header_value(), "gzip");
+        assert_eq!(Encoding::Brotli.extension(), Some("br"));
+        assert_eq!(Encoding::Deflate.extension(), None);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::http::request::HttpMethod;
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    IF_UNMODIFIED_SINCE,
+};
+use std::fmt;
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Entity tag identifying a version of a representation, as described in RFC 9110 section 8.8.3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    /// Weak tags identify representations which are only semantically equivalent
+    pub weak: bool,
+    /// Opaque tag, without its quotes
+    pub tag: String,
+}
+
+impl EntityTag {
+    /// Creates a new strong [`EntityTag`].
+    pub fn strong(tag: &str) -> EntityTag {
+        EntityTag {
+            weak: false,
+            tag: String::from(tag),
+        }
+    }
+
+    /// Creates the strong [`EntityTag`] of a file from its size and modification time.
+    pub fn from_metadata(metadata: &Metadata) -> EntityTag {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        EntityTag::strong(&format!(
+            "{:x}-{:x}.{:x}",
+            metadata.len(),
+            modified.as_secs(),
+            modified.subsec_nanos()
+        ))
+    }
+
+    /// Parses a single entity tag, e.g. `"abc"` or `W/"abc"`.
+    pub fn parse(value: &str) -> Option<EntityTag> {
+        let (weak, value) = match value.strip_prefix("W/") {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+        let tag = value.strip_prefix('"')?.strip_suffix('"')?;
+        // etagc = "!" / %x23-7E / obs-text
+        if tag
+            .bytes()
+            .any(|byte| byte == b'"' || byte < 0x21 || byte == 0x7f)
+        {
+            return None;
+        }
+        Some(EntityTag {
+            weak,
+            tag: String::from(tag),
+        })
+    }
+
+    /// Returns true if both tags are strong and identical.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Returns true if both tags are identical, whether they are weak or not.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+
+    /// Returns the value of the `ETag` header for this tag.
+    pub fn to_header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.to_string())
+            .unwrap_or_else(|_| HeaderValue::from_static("\"\""))
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// Condition of an `If-Match` or `If-None-Match` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TagCondition {
+    /// `*`, matching any current representation
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+/// Parses the entity tags listed in all the values of a header. Returns `None` if the header is
+/// absent. Invalid tags are ignored.
+fn parse_tag_condition(headers: &HeaderMap, name: HeaderName) -> Option<TagCondition> {
+    let mut values = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .peekable();
+    values.peek()?;
+
+    let mut tags = Vec::new();
+    for value in values {
+        for item in value.split(',').map(str::trim) {
+            if item == "*" {
+                return Some(TagCondition::Any);
+            }
+            tags.extend(EntityTag::parse(item));
+        }
+    }
+    Some(TagCondition::Tags(tags))
+}
+
+/// Parses an HTTP date header. Returns `None` if the header is absent or not a valid date, in
+/// which case the condition must be ignored.
+fn parse_date(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    httpdate::parse_http_date(headers.get(name)?.to_str().ok()?).ok()
+}
+
+/// Truncates a time to whole seconds, the resolution of HTTP dates.
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+/// Validators of the selected representation of a resource
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<EntityTag>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Creates the validators of a file. The modification time is truncated to whole seconds and
+    /// set to the current time if it is in the future.
+    pub fn from_metadata(metadata: &Metadata) -> Validators {
+        let now = SystemTime::now();
+        Validators {
+            etag: Some(EntityTag::from_metadata(metadata)),
+            last_modified: metadata
+                .modified()
+                .ok()
+                .map(|modified| truncate_to_seconds(modified.min(now))),
+        }
+    }
+
+    /// Adds the `ETag` and `Last-Modified` headers to a header map.
+    pub fn add_headers(&self, headers: &mut HeaderMap) {
+        if let Some(etag) = &self.etag {
+            headers.insert(http::header::ETAG, etag.to_header_value());
+        }
+        if let Some(value) = self
+            .last_modified
+            .and_then(|date| HeaderValue::from_str(&httpdate::fmt_http_date(date)).ok())
+        {
+            headers.insert(http::header::LAST_MODIFIED, value);
+        }
+    }
+
+    /// Returns true if the `If-Range` condition of a request matches these validators. The
+    /// condition is either a strong entity tag or a date which must equal the modification time.
+    pub fn matches_if_range(&self, value: &HeaderValue) -> bool {
+        let value = match value.to_str() {
+            Ok(value) => value.trim(),
+            Err(_) => return false,
+        };
+        match EntityTag::parse(value) {
+            Some(tag) => self.etag.as_ref().is_some_and(|etag| etag.strong_eq(&tag)),
+            None => match (httpdate::parse_http_date(value), self.last_modified) {
+                (Ok(date), Some(last_modified)) => date == last_modified,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Result of the evaluation of the conditional headers of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// Request must be processed normally
+    Passed,
+    /// Representation did not change, answer with 304
+    NotModified,
+    /// A precondition failed, answer with 412
+    Failed,
+}
+
+/// Evaluates the conditional headers of a request against the validators of an existing
+/// representation, following the order of RFC 9110 section 13.2.2.
+pub fn evaluate_preconditions(
+    method: &HttpMethod,
+    headers: &HeaderMap,
+    validators: &Validators,
+) -> Precondition {
+    let is_get_or_head = matches!(method, HttpMethod::Get | HttpMethod::Head);
+
+    // If-Match uses the strong comparison
+    if let Some(condition) = parse_tag_condition(headers, IF_MATCH) {
+        let matched = match condition {
+            TagCondition::Any => true,
+            TagCondition::Tags(tags) => validators
+                .etag
+                .as_ref()
+                .is_some_and(|etag| tags.iter().any(|tag| tag.strong_eq(etag))),
+        };
+        if !matched {
+            return Precondition::Failed;
+        }
+    } else if let Some(date) = parse_date(headers, IF_UNMODIFIED_SINCE) {
+        if validators
+            .last_modified
+            .is_none_or(|last_modified| last_modified > date)
+        {
+            return Precondition::Failed;
+        }
+    }
+
+    // If-None-Match uses the weak comparison
+    if let Some(condition) = parse_tag_condition(headers, IF_NONE_MATCH) {
+        let matched = match condition {
+            TagCondition::Any => true,
+            TagCondition::Tags(tags) => validators
+                .etag
+                .as_ref()
+                .is_some_and(|etag| tags.iter().any(|tag| tag.weak_eq(etag))),
+        };
+        if matched {
+            return if is_get_or_head {
+                Precondition::NotModified
+            } else {
+                Precondition::Failed
+            };
+        }
+    } else if is_get_or_head {
+        if let (Some(date), Some(last_modified)) = (
+            parse_date(headers, IF_MODIFIED_SINCE),
+            validators.last_modified,
+        ) {
+            if last_modified <= date {
+                return Precondition::NotModified;
+            }
+        }
+    }
+
+    Precondition::Passed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATE: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+    const EARLIER: &str = "Sat, 05 Nov 1994 08:49:37 GMT";
+
+    fn validators() -> Validators {
+        Validators {
+            etag: Some(EntityTag::strong("abc")),
+            last_modified: Some(httpdate::parse_http_date(DATE).expect("")),
+        }
+    }
+
+    fn evaluate(method: HttpMethod, fields: &[(&'static str, &'static str)]) -> Precondition {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        evaluate_preconditions(&method, &headers, &validators())
+    }
+
+    #[test]
+    fn parse_entity_tags() {
+        assert_eq!(EntityTag::parse("\"abc\""), Some(EntityTag::strong("abc")));
+        assert_eq!(
+            EntityTag::parse("W/\"abc\""),
+            Some(EntityTag {
+                weak: true,
+                tag: String::from("abc")
+            })
+        );
+        assert_eq!(EntityTag::parse("abc"), None);
+        assert_eq!(EntityTag::parse("\"a\"b\""), None);
+        assert_eq!(EntityTag::strong("abc").to_string(), "\"abc\"");
+    }
+
+    #[test]
+    fn if_none_match_returns_not_modified() {
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-none-match", "\"xyz\", W/\"abc\"")]),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Head, &[("if-none-match", "*")]),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-none-match", "\"xyz\"")]),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Put, &[("if-none-match", "\"abc\"")]),
+            Precondition::Failed
+        );
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        assert_eq!(
+            evaluate(
+                HttpMethod::Get,
+                &[("if-none-match", "\"xyz\""), ("if-modified-since", DATE)]
+            ),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-modified-since", DATE)]),
+            Precondition::NotModified
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-modified-since", EARLIER)]),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-modified-since", "not a date")]),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn if_match_uses_strong_comparison() {
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-match", "\"abc\"")]),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-match", "W/\"abc\"")]),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-match", "*")]),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn if_match_takes_precedence_over_if_unmodified_since() {
+        assert_eq!(
+            evaluate(
+                HttpMethod::Get,
+                &[("if-match", "\"abc\""), ("if-unmodified-since", EARLIER)]
+            ),
+            Precondition::Passed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-unmodified-since", EARLIER)]),
+            Precondition::Failed
+        );
+        assert_eq!(
+            evaluate(HttpMethod::Get, &[("if-unmodified-since", DATE)]),
+            Precondition::Passed
+        );
+    }
+
+    #[test]
+    fn failed_if_match_is_evaluated_before_if_none_match() {
+        assert_eq!(
+            evaluate(
+                HttpMethod::Get,
+                &[("if-match", "\"xyz\""), ("if-none-match", "\"abc\"")]
+            ),
+            Precondition::Failed
+        );
+    }
+
+    #[test]
+    fn if_range_matches_strong_tag_or_exact_date() {
+        let validators = validators();
+
+        assert!(validators.matches_if_range(&HeaderValue::from_static("\"abc\"")));
+        assert!(!validators.matches_if_range(&HeaderValue::from_static("W/\"abc\"")));
+        assert!(validators.matches_if_range(&HeaderValue::from_static(DATE)));
+        assert!(!validators.matches_if_range(&HeaderValue::from_static(EARLIER)));
+    }
+}
@@ -1,37 +1,86 @@
+use crate::http::mime_types::default_mime_types;
+use crate::http::range::ByteRange;
+use crate::http::response::Body;
+use http::header::HeaderValue;
 use mime::Mime;
-use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
-pub type Message = Vec<u8>;
+/// File opened to be sent as the body of a response, without being loaded in memory
+#[derive(Debug)]
+pub struct FileContent {
+    pub file: File,
+    /// Length of the file when it was opened
+    pub length: u64,
+}
+
+impl FileContent {
+    /// Reads up to `limit` bytes from the start of the file, then goes back to the start.
+    pub fn read_prefix(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let mut prefix = Vec::with_capacity(limit.min(self.length as usize));
+        (&mut self.file)
+            .take(limit as u64)
+            .read_to_end(&mut prefix)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        Ok(prefix)
+    }
 
-/// Load content as a vector of bytes from the provided URI. Could be images, HTML file, etc.
-/// Returns std::io::Error if loading failed
-pub fn load_content_from_uri(uri: &str) -> Result<Message, std::io::Error> {
-    let path = Path::new(uri);
-    fs::read(path)
+    /// Returns a body containing a range of the file.
+    pub fn into_range_body(mut self, range: &ByteRange) -> io::Result<Body> {
+        self.file.seek(SeekFrom::Start(range.first))?;
+        Ok(Body::File {
+            file: self.file,
+            length: range.len(),
+        })
+    }
 }
 
-/// Returns a Mime type based on the filename. Returns text/plain by default.
-pub fn find_mimetype(filename: &str) -> Mime {
-    let parts: Vec<&str> = filename.split('.').collect();
-
-    let result = match parts.last() {
-        Some(v) => match *v {
-            "html" => mime::TEXT_HTML,
-            "png" => mime::IMAGE_PNG,
-            "jpg" => mime::IMAGE_JPEG,
-            "json" => mime::APPLICATION_JSON,
-            &_ => mime::TEXT_PLAIN,
-        },
-        None => mime::TEXT_PLAIN,
-    };
+impl From<FileContent> for Body {
+    fn from(content: FileContent) -> Self {
+        Body::File {
+            file: content.file,
+            length: content.length,
+        }
+    }
+}
 
-    result
+/// Opens the file at the provided URI so that its content can be streamed. Could be images, HTML
+/// file, etc.
+/// Returns std::io::Error if the file could not be opened or is not a regular file
+pub fn load_content_from_uri<P: AsRef<Path>>(uri: P) -> Result<FileContent, io::Error> {
+    let file = File::open(uri)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Content is not a regular file",
+        ));
+    }
+    Ok(FileContent {
+        file,
+        length: metadata.len(),
+    })
 }
 
-/// Returns a string of a standard content type line based on the Mime type.
-pub fn build_content_type(mime: &Mime) -> String {
-    format!("Content-Type: {}/{}\r\n", mime.type_(), mime.subtype())
+/// Returns a Mime type based on the filename, using the default table of extensions. Returns
+/// text/plain by default.
+pub fn find_mimetype(filename: &str) -> Mime {
+    default_mime_types()
+        .lookup(filename)
+        .unwrap_or(mime::TEXT_PLAIN)
+}
+
+/// Returns the value of the Content-Type header based on the Mime type. Text types are declared
+/// as UTF-8 unless they already specify a charset.
+pub fn build_content_type(mime: &Mime) -> HeaderValue {
+    let value = if mime.type_() == mime::TEXT && mime.get_param(mime::CHARSET).is_none() {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    };
+    HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static("text/plain"))
 }
 
 #[cfg(test)]
@@ -39,6 +88,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn test_load_existing_file() {
         let uri = "example/hello.html";
 
@@ -48,6 +98,27 @@ mod tests {
     }
 
     #[test]
+    fn test_read_file_range() {
+        let mut content = load_content_from_uri("example/hello.html").expect("");
+
+        assert_eq!(content.read_prefix(9).expect(""), b"<!DOCTYPE");
+        match content.into_range_body(&ByteRange { first: 2, last: 8 }) {
+            Ok(Body::File { mut file, length }) => {
+                let mut bytes = Vec::new();
+                (&mut file).take(length).read_to_end(&mut bytes).expect("");
+                assert_eq!(bytes, b"DOCTYPE");
+            }
+            other => panic!("Unexpected body {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_load_directory() {
+        assert!(load_content_from_uri("example").is_err());
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn test_load_non_existing_png_file() {
         let uri = "non_existing.png";
 
@@ -82,4 +153,26 @@ mod tests {
 
         assert_eq!(result, mime::TEXT_PLAIN);
     }
+
+    #[test]
+    fn test_find_multi_dot_mime_type() {
+        let filepath = "example/archive.TAR.GZ";
+
+        let result = find_mimetype(filepath);
+
+        assert_eq!(result.essence_str(), "application/gzip");
+    }
+
+    #[test]
+    fn test_build_content_type_with_charset() {
+        assert_eq!(
+            build_content_type(&mime::TEXT_HTML),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            build_content_type(&mime::TEXT_PLAIN_UTF_8),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(build_content_type(&mime::IMAGE_PNG), "image/png");
+    }
 }
@@ -0,0 +1,308 @@
+use crate::http::uri::percent_decode;
+use http::StatusCode;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Kind of error preventing a request target from being resolved to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveErrorKind {
+    /// Target is not a valid path
+    InvalidPath,
+    /// Path leads outside of the document root
+    Forbidden,
+    /// No file exists at this path
+    NotFound,
+}
+
+/// Error returned when a request target cannot be resolved to a file below the document root
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    kind: ResolveErrorKind,
+    msg: String,
+}
+
+impl ResolveError {
+    fn new(kind: ResolveErrorKind, msg: &str) -> ResolveError {
+        ResolveError {
+            kind,
+            msg: String::from(msg),
+        }
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> ResolveErrorKind {
+        self.kind
+    }
+
+    /// Returns the status code of the response sent when this error occurs.
+    pub fn status_code(&self) -> StatusCode {
+        match self.kind {
+            ResolveErrorKind::InvalidPath => StatusCode::BAD_REQUEST,
+            ResolveErrorKind::Forbidden => StatusCode::FORBIDDEN,
+            ResolveErrorKind::NotFound => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+/// Directory from which files are served
+#[derive(Debug, Clone)]
+pub struct DocumentRoot {
+    path: PathBuf,
+}
+
+impl DocumentRoot {
+    /// Creates a new [`DocumentRoot`] serving the files below the provided directory.
+    pub fn new<P: Into<PathBuf>>(path: P) -> DocumentRoot {
+        DocumentRoot { path: path.into() }
+    }
+
+    /// Returns the directory from which files are served.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Resolves the percent-encoded path of a request target to the canonical path of a file
+    /// below the document root. The path is percent-decoded and its `.` and `..` segments are
+    /// removed before the file is looked up. Paths leading outside of the root, including through
+    /// symbolic links, are rejected.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, ResolveError> {
+        if !path.starts_with('/') {
+            return Err(ResolveError::new(
+                ResolveErrorKind::InvalidPath,
+                "Target is not an absolute path",
+            ));
+        }
+
+        let decoded = percent_decode(path)
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+            .ok_or_else(|| {
+                ResolveError::new(
+                    ResolveErrorKind::InvalidPath,
+                    "Target is not a valid percent-encoded path",
+                )
+            })?;
+
+        let mut relative = PathBuf::new();
+        for segment in normalize(&decoded)? {
+            relative.push(segment);
+        }
+
+        let root = self.path.canonicalize().map_err(|_| {
+            ResolveError::new(ResolveErrorKind::NotFound, "Document root does not exist")
+        })?;
+        let resolved = root
+            .join(relative)
+            .canonicalize()
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::PermissionDenied => {
+                    ResolveError::new(ResolveErrorKind::Forbidden, "Access to file is denied")
+                }
+                _ => ResolveError::new(ResolveErrorKind::NotFound, "File does not exist"),
+            })?;
+
+        // Symbolic links are followed by canonicalize, so they may lead anywhere
+        if !resolved.starts_with(&root) {
+            return Err(ResolveError::new(
+                ResolveErrorKind::Forbidden,
+                "Path leads outside of the document root",
+            ));
+        }
+        Ok(resolved)
+    }
+
+    /// Returns the first of the index files found in a directory resolved by
+    /// [`DocumentRoot::resolve`]. Index files leading outside of the root are ignored.
+    pub fn find_index(&self, dir: &Path, index_files: &[String]) -> Option<PathBuf> {
+        let root = self.path.canonicalize().ok()?;
+        index_files
+            .iter()
+            .filter_map(|name| dir.join(name).canonicalize().ok())
+            .find(|index| index.starts_with(&root) && index.is_file())
+    }
+
+    /// Returns the file named like a file resolved by [`DocumentRoot::resolve`] followed by an
+    /// extension, e.g. `page.html.gz` for `page.html`. Files leading outside of the root are
+    /// ignored.
+    pub fn find_sibling(&self, file: &Path, extension: &str) -> Option<PathBuf> {
+        let root = self.path.canonicalize().ok()?;
+        let mut name = file.as_os_str().to_os_string();
+        name.push(".");
+        name.push(extension);
+        let sibling = PathBuf::from(name).canonicalize().ok()?;
+        (sibling.starts_with(&root) && sibling.is_file()).then_some(sibling)
+    }
+}
+
+/// Splits a decoded path into its segments, removing `.` and `..` segments as described in
+/// RFC 3986 section 5.2.4. Returns an error if a `..` segment would go above the root or if a
+/// segment could be interpreted differently by the file system.
+fn normalize(path: &str) -> Result<Vec<&str>, ResolveError> {
+    let mut segments = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(ResolveError::new(
+                        ResolveErrorKind::Forbidden,
+                        "Path leads outside of the document root",
+                    ));
+                }
+            }
+            // Backslashes are separators on Windows and drive prefixes make a path absolute
+            _ if segment.contains(['\\', '\0']) || (cfg!(windows) && segment.contains(':')) => {
+                return Err(ResolveError::new(
+                    ResolveErrorKind::Forbidden,
+                    "Path contains a forbidden character",
+                ));
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_test_dir;
+    use std::fs;
+
+    /// Creates an empty directory for a test, containing `public/index.html` and `secret.txt`.
+    fn setup(name: &str) -> PathBuf {
+        let dir = empty_test_dir(name);
+        fs::create_dir_all(dir.join("public/docs")).expect("");
+        fs::write(dir.join("public/index.html"), "index").expect("");
+        fs::write(dir.join("secret.txt"), "secret").expect("");
+        dir
+    }
+
+    fn resolve(dir: &Path, target: &str) -> Result<PathBuf, ResolveErrorKind> {
+        DocumentRoot::new(dir.join("public"))
+            .resolve(target)
+            .map_err(|error| error.kind())
+    }
+
+    #[test]
+    fn resolve_file_below_root() {
+        let dir = setup("resolve");
+
+        let result = resolve(&dir, "/docs/../index.html");
+
+        assert_eq!(
+            result,
+            Ok(dir.join("public/index.html").canonicalize().expect(""))
+        );
+        assert_eq!(
+            resolve(&dir, "/missing.html"),
+            Err(ResolveErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn reject_dot_segments_escaping_root() {
+        let dir = setup("dot-segments");
+
+        assert_eq!(
+            resolve(&dir, "/../secret.txt"),
+            Err(ResolveErrorKind::Forbidden)
+        );
+        assert_eq!(
+            resolve(&dir, "/docs/../../secret.txt"),
+            Err(ResolveErrorKind::Forbidden)
+        );
+    }
+
+    #[test]
+    fn reject_encoded_dot_segments_escaping_root() {
+        let dir = setup("encoded-dot-segments");
+
+        assert_eq!(
+            resolve(&dir, "/%2e%2e/secret.txt"),
+            Err(ResolveErrorKind::Forbidden)
+        );
+        assert_eq!(
+            resolve(&dir, "/..%2fsecret.txt"),
+            Err(ResolveErrorKind::Forbidden)
+        );
+        // Decoding is done once, so a double-encoded segment is a plain file name
+        assert_eq!(
+            resolve(&dir, "/%252e%252e/secret.txt"),
+            Err(ResolveErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn reject_absolute_and_windows_paths() {
+        let dir = setup("absolute");
+        let secret = dir.join("secret.txt");
+
+        assert_eq!(
+            resolve(&dir, &format!("/{}", secret.display())),
+            Err(ResolveErrorKind::NotFound)
+        );
+        assert_eq!(
+            resolve(&dir, "/..\\secret.txt"),
+            Err(ResolveErrorKind::Forbidden)
+        );
+        if cfg!(windows) {
+            assert_eq!(
+                resolve(&dir, "/C:/secret.txt"),
+                Err(ResolveErrorKind::Forbidden)
+            );
+        }
+        assert_eq!(
+            resolve(&dir, "/index.html%00.png"),
+            Err(ResolveErrorKind::Forbidden)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_paths() {
+        let dir = setup("invalid");
+
+        assert_eq!(
+            resolve(&dir, "index.html"),
+            Err(ResolveErrorKind::InvalidPath)
+        );
+        assert_eq!(
+            resolve(&dir, "/index%2"),
+            Err(ResolveErrorKind::InvalidPath)
+        );
+        assert_eq!(resolve(&dir, "/%ff"), Err(ResolveErrorKind::InvalidPath));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reject_symlink_escaping_root() {
+        let dir = setup("symlink");
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("public/link.txt")).expect("");
+        std::os::unix::fs::symlink(dir.join("public/index.html"), dir.join("public/inner.html"))
+            .expect("");
+
+        assert_eq!(resolve(&dir, "/link.txt"), Err(ResolveErrorKind::Forbidden));
+        assert!(resolve(&dir, "/inner.html").is_ok());
+    }
+
+    #[test]
+    fn find_sibling_file() {
+        let dir = setup("sibling");
+        fs::write(dir.join("public/index.html.gz"), "compressed").expect("");
+        let root = DocumentRoot::new(dir.join("public"));
+        let index = root.resolve("/index.html").expect("");
+
+        assert_eq!(
+            root.find_sibling(&index, "gz"),
+            Some(dir.join("public/index.html.gz").canonicalize().expect(""))
+        );
+        assert_eq!(root.find_sibling(&index, "br"), None);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::http::uri::QueryParams;
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Format in which a directory listing is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingFormat {
+    Html,
+    Json,
+}
+
+/// Attribute used to sort the entries of a directory listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+}
+
+/// Options of a directory listing, selected with the `format`, `sort` and `order` query
+/// parameters, e.g. `?format=json&sort=size&order=desc`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListingOptions {
+    pub format: ListingFormat,
+    pub sort: SortKey,
+    pub descending: bool,
+}
+
+impl Default for ListingOptions {
+    fn default() -> Self {
+        ListingOptions {
+            format: ListingFormat::Html,
+            sort: SortKey::Name,
+            descending: false,
+        }
+    }
+}
+
+impl ListingOptions {
+    /// Reads the options from the query parameters of a request. Unknown parameters and values
+    /// are ignored.
+    pub fn from_query(params: &QueryParams) -> ListingOptions {
+        let mut options = ListingOptions::default();
+
+        for (name, value) in params.iter() {
+            match (name, value) {
+                ("format", "html") => options.format = ListingFormat::Html,
+                ("format", "json") => options.format = ListingFormat::Json,
+                ("sort", "name") => options.sort = SortKey::Name,
+                ("sort", "size") => options.sort = SortKey::Size,
+                ("sort", "mtime") => options.sort = SortKey::Modified,
+                ("order", "asc") => options.descending = false,
+                ("order", "desc") => options.descending = true,
+                _ => (),
+            }
+        }
+        options
+    }
+}
+
+/// File or directory shown in a directory listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub is_dir: bool,
+    /// Size in bytes, zero for directories
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Reads the entries of a directory. Entries which are not valid UTF-8 are skipped.
+pub fn read_entries(dir: &Path) -> io::Result<Vec<DirectoryEntry>> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        // Metadata of the link target, so that links to directories are shown as directories
+        let metadata = match fs::metadata(entry.path()) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        entries.push(DirectoryEntry {
+            name,
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Sorts entries following the listing options. Directories are always listed first.
+pub fn sort_entries(entries: &mut [DirectoryEntry], options: &ListingOptions) {
+    entries.sort_by(|a, b| {
+        let ordering = match options.sort {
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let ordering = if options.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+
+        match (a.is_dir, b.is_dir) {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => ordering,
+        }
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a file name so that it can be used as a path segment in a link.
+fn encode_segment(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Renders a directory listing as an HTML page. `path` is the decoded path of the directory,
+/// shown as title. Column headers link to the listing sorted by this column.
+pub fn render_html(path: &str, entries: &[DirectoryEntry], options: &ListingOptions) -> String {
+    let title = escape_html(path);
+    let header = |key: SortKey, sort: &str, title: &str| {
+        let order = if options.sort == key && !options.descending {
+            "desc"
+        } else {
+            "asc"
+        };
+        format!(
+            "<th><a href=\"?sort={}&amp;order={}\">{}</a></th>",
+            sort, order, title
+        )
+    };
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of {path}</title>\n</head>\n<body>\n<h1>Index of {path}</h1>\n<table>\n<tr>{}{}{}</tr>\n",
+        header(SortKey::Name, "name", "Name"),
+        header(SortKey::Size, "size", "Size"),
+        header(SortKey::Modified, "mtime", "Last modified"),
+        path = title
+    );
+    if path != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let size = if entry.is_dir {
+            String::from("-")
+        } else {
+            entry.size.to_string()
+        };
+        let modified = entry
+            .modified
+            .map_or(String::new(), httpdate::fmt_http_date);
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            encode_segment(&entry.name),
+            suffix,
+            escape_html(&entry.name),
+            suffix,
+            size,
+            modified
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Renders a directory listing as a JSON array. Modification times are given in seconds since
+/// the Unix epoch.
+pub fn render_json(entries: &[DirectoryEntry]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let modified = entry
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(String::from("null"), |modified| {
+                    modified.as_secs().to_string()
+                });
+            format!(
+                "{{\"name\":\"{}\",\"type\":\"{}\",\"size\":{},\"modified\":{}}}",
+                escape_json(&entry.name),
+                if entry.is_dir { "directory" } else { "file" },
+                entry.size,
+                modified
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_test_dir;
+    use std::time::Duration;
+
+    fn entry(name: &str, is_dir: bool, size: u64, modified: u64) -> DirectoryEntry {
+        DirectoryEntry {
+            name: String::from(name),
+            is_dir,
+            size,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(modified)),
+        }
+    }
+
+    fn names(entries: &[DirectoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_listing_options() {
+        let options = ListingOptions::from_query(&QueryParams::parse(
+            "format=json&sort=mtime&order=desc&other=1",
+        ));
+
+        assert_eq!(
+            options,
+            ListingOptions {
+                format: ListingFormat::Json,
+                sort: SortKey::Modified,
+                descending: true,
+            }
+        );
+        assert_eq!(
+            ListingOptions::from_query(&QueryParams::parse("")),
+            ListingOptions::default()
+        );
+    }
+
+    #[test]
+    fn sort_entries_with_directories_first() {
+        let mut entries = vec![
+            entry("b.txt", false, 10, 3),
+            entry("docs", true, 0, 1),
+            entry("a.txt", false, 20, 2),
+        ];
+
+        sort_entries(&mut entries, &ListingOptions::default());
+        assert_eq!(names(&entries), vec!["docs", "a.txt", "b.txt"]);
+
+        sort_entries(
+            &mut entries,
+            &ListingOptions::from_query(&QueryParams::parse("sort=size&order=desc")),
+        );
+        assert_eq!(names(&entries), vec!["docs", "a.txt", "b.txt"]);
+
+        sort_entries(
+            &mut entries,
+            &ListingOptions::from_query(&QueryParams::parse("sort=mtime&order=desc")),
+        );
+        assert_eq!(names(&entries), vec!["docs", "b.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn render_html_escapes_names() {
+        let entries = vec![
+            entry("<script>.html", false, 5, 0),
+            entry("my docs", true, 0, 0),
+        ];
+
+        let html = render_html("/files/", &entries, &ListingOptions::default());
+
+        assert!(html.contains("<title>Index of /files/</title>"));
+        assert!(html.contains("<a href=\"%3Cscript%3E.html\">&lt;script&gt;.html</a>"));
+        assert!(html.contains("<a href=\"my%20docs/\">my docs/</a>"));
+        assert!(html.contains("<a href=\"?sort=name&amp;order=desc\">Name</a>"));
+    }
+
+    #[test]
+    fn render_json_listing() {
+        let entries = vec![entry("a\"b.txt", false, 5, 60), entry("docs", true, 0, 0)];
+
+        let json = render_json(&entries);
+
+        assert_eq!(
+            json,
+            "[{\"name\":\"a\\\"b.txt\",\"type\":\"file\",\"size\":5,\"modified\":60},\
+             {\"name\":\"docs\",\"type\":\"directory\",\"size\":0,\"modified\":0}]"
+        );
+    }
+
+    #[test]
+    fn read_directory_entries() {
+        let dir = empty_test_dir("listing");
+        fs::create_dir_all(dir.join("docs")).expect("");
+        fs::write(dir.join("hello.txt"), "hello").expect("");
+
+        let mut entries = read_entries(&dir).expect("");
+        sort_entries(&mut entries, &ListingOptions::default());
+
+        assert_eq!(names(&entries), vec!["docs", "hello.txt"]);
+        assert!(entries[0].is_dir);
+        assert_eq!(entries[1].size, 5);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::http::request::HttpRequest;
+use crate::http::response::HttpResponse;
+use crate::http::server::{Handler, ServerError};
+use http::header::{HeaderMap, HeaderValue, IntoHeaderName};
+use http::StatusCode;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info};
+
+/// Name of the header carrying server metrics, see the Server Timing specification
+const SERVER_TIMING: &str = "server-timing";
+
+/// Logic wrapped around the handling of requests, e.g. logging or authentication.
+///
+/// A middleware receives the request and the rest of the pipeline as [`Next`]. Code before the
+/// call to [`Next::run`] acts on the request, code after it on the response. Returning without
+/// calling it short-circuits the pipeline. A modified request is passed on by cloning it. Closures
+/// taking the request and [`Next`] implement this trait.
+pub trait Middleware: Send + Sync {
+    /// Returns the response to a request, usually by calling `next`.
+    fn handle(&self, request: &HttpRequest, next: Next) -> Result<HttpResponse, ServerError>;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(&HttpRequest, Next) -> Result<HttpResponse, ServerError> + Send + Sync,
+{
+    fn handle(&self, request: &HttpRequest, next: Next) -> Result<HttpResponse, ServerError> {
+        self(request, next)
+    }
+}
+
+/// Remaining middlewares and handler of a [`Pipeline`]
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn Middleware>],
+    handler: &'a dyn Handler,
+}
+
+impl<'a> Next<'a> {
+    /// Passes the request to the next middleware, or to the handler after the last middleware.
+    pub fn run(self, request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        match self.layers.split_first() {
+            Some((layer, layers)) => layer.handle(
+                request,
+                Next {
+                    layers,
+                    handler: self.handler,
+                },
+            ),
+            None => self.handler.handle(request),
+        }
+    }
+}
+
+/// Handler wrapped in a stack of middlewares.
+///
+/// Middlewares run in the order they are added: the first one sees the request first and the
+/// response last.
+/// # Example
+///
+/// ```
+/// use http_server::http::middleware::{Logger, Pipeline, Recover, SetHeaders};
+/// use http_server::http::request::HttpRequest;
+/// use http_server::http::response::HttpResponse;
+/// use http::header::{HeaderValue, X_FRAME_OPTIONS};
+///
+/// let pipeline = Pipeline::new(|_request: &HttpRequest| Ok(HttpResponse::builder().build()))
+///     .layer(Logger::new())
+///     .layer(Recover::new())
+///     .layer(SetHeaders::new().header(X_FRAME_OPTIONS, HeaderValue::from_static("DENY")));
+/// ```
+#[derive(Clone)]
+pub struct Pipeline {
+    layers: Vec<Arc<dyn Middleware>>,
+    handler: Arc<dyn Handler>,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
+
+impl Pipeline {
+    /// Creates a new [`Pipeline`] without middleware around the handler.
+    pub fn new<H: Handler + 'static>(handler: H) -> Pipeline {
+        Pipeline {
+            layers: Vec::new(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Adds a middleware after the ones already added.
+    pub fn layer<M: Middleware + 'static>(mut self, middleware: M) -> Pipeline {
+        self.layers.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds a closure taking the request and [`Next`] after the middlewares already added.
+    pub fn layer_fn<F>(self, middleware: F) -> Pipeline
+    where
+        F: Fn(&HttpRequest, Next) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.layer(middleware)
+    }
+}
+
+impl Handler for Pipeline {
+    fn handle(&self, request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        Next {
+            layers: &self.layers,
+            handler: self.handler.as_ref(),
+        }
+        .run(request)
+    }
+}
+
+/// Emits an `info` event with the method, target, status and duration of each request
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger;
+
+impl Logger {
+    /// Creates a new [`Logger`].
+    pub fn new() -> Logger {
+        Logger
+    }
+}
+
+impl Middleware for Logger {
+    fn handle(&self, request: &HttpRequest, next: Next) -> Result<HttpResponse, ServerError> {
+        let start = Instant::now();
+        let result = next.run(request);
+        match &result {
+            Ok(response) => info!(
+                status = response.status().as_u16(),
+                duration = ?start.elapsed(),
+                "{} {}",
+                request.line.method,
+                request.line.uri
+            ),
+            Err(error) => error!(
+                "{} {} failed: {}",
+                request.line.method, request.line.uri, error
+            ),
+        }
+        result
+    }
+}
+
+/// Adds the time spent handling a request to the response, as a `Server-Timing` metric
+#[derive(Debug, Clone)]
+pub struct Timing {
+    metric: String,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            metric: String::from("app"),
+        }
+    }
+}
+
+impl Timing {
+    /// Creates a new [`Timing`] reporting the `app` metric.
+    pub fn new() -> Timing {
+        Timing::default()
+    }
+
+    /// Creates a new [`Timing`] reporting a metric with another name, which must be a valid
+    /// token.
+    pub fn with_metric(metric: &str) -> Timing {
+        Timing {
+            metric: String::from(metric),
+        }
+    }
+}
+
+impl Middleware for Timing {
+    fn handle(&self, request: &HttpRequest, next: Next) -> Result<HttpResponse, ServerError> {
+        let start = Instant::now();
+        let mut response = next.run(request)?;
+        let duration = start.elapsed().as_secs_f64() * 1000.0;
+        if let Ok(value) = HeaderValue::from_str(&format!("{};dur={:.3}", self.metric, duration)) {
+            response.headers_mut().append(SERVER_TIMING, value);
+        }
+        Ok(response)
+    }
+}
+
+/// Adds header fields to every response, e.g. security headers
+#[derive(Debug, Clone, Default)]
+pub struct SetHeaders {
+    headers: HeaderMap,
+    defaults: HeaderMap,
+}
+
+impl SetHeaders {
+    /// Creates a new [`SetHeaders`] without header.
+    pub fn new() -> SetHeaders {
+        SetHeaders::default()
+    }
+
+    /// Sets a header field, replacing the value set by the handler if any.
+    pub fn header<K: IntoHeaderName>(mut self, name: K, value: HeaderValue) -> SetHeaders {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets a header field unless the handler already set it.
+    pub fn default_header<K: IntoHeaderName>(mut self, name: K, value: HeaderValue) -> SetHeaders {
+        self.defaults.insert(name, value);
+        self
+    }
+}
+
+impl Middleware for SetHeaders {
+    fn handle(&self, request: &HttpRequest, next: Next) -> Result<HttpResponse, ServerError> {
+        let mut response = next.run(request)?;
+        let headers = response.headers_mut();
+        for (name, value) in &self.headers {
+            headers.insert(name, value.clone());
+        }
+        for (name, value) in &self.defaults {
+            if !headers.contains_key(name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Answers `500 Internal Server Error` when the rest of the pipeline fails or panics, instead of
+/// closing the connection without response
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Recover;
+
+impl Recover {
+    /// Creates a new [`Recover`].
+    pub fn new() -> Recover {
+        Recover
+    }
+}
+
+impl Middleware for Recover {
+    fn handle(&self, request: &HttpRequest, next: Next) -> Result<HttpResponse, ServerError> {
+        match panic::catch_unwind(AssertUnwindSafe(|| next.run(request))) {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(error)) => {
+                error!("Error when handling request: {}", error);
+                Ok(build_internal_error_response())
+            }
+            Err(_) => {
+                error!(
+                    "Panic when handling {} {}",
+                    request.line.method, request.line.uri
+                );
+                Ok(build_internal_error_response())
+            }
+        }
+    }
+}
+
+/// Generates the response sent back when handling a request failed
+fn build_internal_error_response() -> HttpResponse {
+    HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::{CACHE_CONTROL, X_FRAME_OPTIONS};
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    fn request(target: &str) -> HttpRequest {
+        HttpRequest::from_str(&format!("GET {} HTTP/1.1\r\n\r\n", target)).unwrap()
+    }
+
+    fn respond(_request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        Ok(HttpResponse::builder().body("handler").build())
+    }
+
+    #[test]
+    fn run_layers_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let layer = |name: &'static str| {
+            let calls = Arc::clone(&calls);
+            move |request: &HttpRequest, next: Next| {
+                calls.lock().unwrap().push(format!("before {}", name));
+                let response = next.run(request);
+                calls.lock().unwrap().push(format!("after {}", name));
+                response
+            }
+        };
+        let pipeline = Pipeline::new(respond)
+            .layer_fn(layer("a"))
+            .layer_fn(layer("b"));
+
+        pipeline.handle(&request("/")).unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["before a", "before b", "after b", "after a"]
+        );
+    }
+
+    #[test]
+    fn short_circuit_and_modify_request() {
+        let pipeline = Pipeline::new(|request: &HttpRequest| {
+            Ok(HttpResponse::builder()
+                .body(request.line.uri.as_str())
+                .build())
+        })
+        .layer_fn(|request, next| {
+            if request.path() == "/private" {
+                return Ok(HttpResponse::new(StatusCode::FORBIDDEN));
+            }
+            let mut request = request.clone();
+            request.line.uri = format!("/v1{}", request.line.uri);
+            request
+                .headers
+                .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+            next.run(&request)
+        });
+
+        let forbidden = pipeline.handle(&request("/private")).unwrap();
+        let rewritten = pipeline.handle(&request("/users")).unwrap();
+
+        assert_eq!(forbidden.status(), StatusCode::FORBIDDEN);
+        assert!(rewritten
+            .into_bytes()
+            .unwrap()
+            .ends_with(b"\r\n\r\n/v1/users"));
+    }
+
+    #[test]
+    fn set_response_headers() {
+        let pipeline = Pipeline::new(|_: &HttpRequest| {
+            Ok(HttpResponse::builder()
+                .header(CACHE_CONTROL, HeaderValue::from_static("max-age=60"))
+                .header(X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"))
+                .build())
+        })
+        .layer(
+            SetHeaders::new()
+                .header(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"))
+                .default_header(CACHE_CONTROL, HeaderValue::from_static("no-store"))
+                .default_header(
+                    "x-content-type-options",
+                    HeaderValue::from_static("nosniff"),
+                ),
+        );
+
+        let response = pipeline.handle(&request("/")).unwrap();
+
+        assert_eq!(response.headers()[X_FRAME_OPTIONS], "DENY");
+        assert_eq!(response.headers()[CACHE_CONTROL], "max-age=60");
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+    }
+
+    #[test]
+    fn add_server_timing() {
+        let pipeline = Pipeline::new(respond)
+            .layer(Logger::new())
+            .layer(Timing::with_metric("handler"));
+
+        let response = pipeline.handle(&request("/")).unwrap();
+
+        let timing = response.headers()[SERVER_TIMING].to_str().unwrap();
+        assert!(timing.starts_with("handler;dur="), "{}", timing);
+    }
+
+    #[test]
+    fn recover_from_errors_and_panics() {
+        let failing = Pipeline::new(|_: &HttpRequest| Err(ServerError::new("Test error")))
+            .layer(Recover::new());
+        let panicking = Pipeline::new(|request: &HttpRequest| {
+            if request.path() == "/panic" {
+                panic!("Test panic");
+            }
+            respond(request)
+        })
+        .layer(Recover::new());
+
+        assert_eq!(
+            failing.handle(&request("/")).unwrap().status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            panicking.handle(&request("/panic")).unwrap().status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            panicking.handle(&request("/")).unwrap().status(),
+            StatusCode::OK
+        );
+    }
+}
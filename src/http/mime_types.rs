@@ -0,0 +1,321 @@
+use mime::Mime;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Extensions known by default, with the MIME type of the files using them
+const DEFAULT_TYPES: &[(&str, &str)] = &[
+    // Text
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("shtml", "text/html"),
+    ("xhtml", "application/xhtml+xml"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("tsv", "text/tab-separated-values"),
+    ("txt", "text/plain"),
+    ("text", "text/plain"),
+    ("log", "text/plain"),
+    ("conf", "text/plain"),
+    ("ini", "text/plain"),
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("rtf", "application/rtf"),
+    ("ics", "text/calendar"),
+    ("vcf", "text/vcard"),
+    ("vtt", "text/vtt"),
+    ("srt", "application/x-subrip"),
+    ("xml", "application/xml"),
+    ("xsl", "application/xml"),
+    ("dtd", "application/xml-dtd"),
+    ("rss", "application/rss+xml"),
+    ("atom", "application/atom+xml"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("toml", "application/toml"),
+    // Scripts and data
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("cjs", "text/javascript"),
+    ("json", "application/json"),
+    ("jsonld", "application/ld+json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+    ("sh", "application/x-sh"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("c", "text/x-c"),
+    ("h", "text/x-c"),
+    ("java", "text/x-java-source"),
+    // Images
+    ("png", "image/png"),
+    ("apng", "image/apng"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("jpe", "image/jpeg"),
+    ("jfif", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("heic", "image/heic"),
+    ("heif", "image/heif"),
+    ("bmp", "image/bmp"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("cur", "image/x-icon"),
+    ("svg", "image/svg+xml"),
+    ("svgz", "image/svg+xml"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("jxl", "image/jxl"),
+    ("psd", "image/vnd.adobe.photoshop"),
+    // Fonts
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("ttc", "font/collection"),
+    ("eot", "application/vnd.ms-fontobject"),
+    // Audio
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("aac", "audio/aac"),
+    ("oga", "audio/ogg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("weba", "audio/webm"),
+    ("flac", "audio/flac"),
+    ("mid", "audio/midi"),
+    ("midi", "audio/midi"),
+    // Video
+    ("mp4", "video/mp4"),
+    ("m4v", "video/mp4"),
+    ("mpeg", "video/mpeg"),
+    ("mpg", "video/mpeg"),
+    ("ogv", "video/ogg"),
+    ("webm", "video/webm"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("mkv", "video/x-matroska"),
+    ("3gp", "video/3gpp"),
+    ("ts", "video/mp2t"),
+    ("m3u8", "application/vnd.apple.mpegurl"),
+    ("mpd", "application/dash+xml"),
+    // Documents
+    ("pdf", "application/pdf"),
+    ("epub", "application/epub+zip"),
+    ("doc", "application/msword"),
+    (
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("xls", "application/vnd.ms-excel"),
+    (
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    (
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    ("odt", "application/vnd.oasis.opendocument.text"),
+    ("ods", "application/vnd.oasis.opendocument.spreadsheet"),
+    ("odp", "application/vnd.oasis.opendocument.presentation"),
+    // Archives
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("tar.gz", "application/gzip"),
+    ("tar.bz2", "application/x-bzip2"),
+    ("tar.xz", "application/x-xz"),
+    ("tar.zst", "application/zstd"),
+    ("bz2", "application/x-bzip2"),
+    ("xz", "application/x-xz"),
+    ("zst", "application/zstd"),
+    ("br", "application/x-brotli"),
+    ("7z", "application/x-7z-compressed"),
+    ("rar", "application/vnd.rar"),
+    ("jar", "application/java-archive"),
+    ("deb", "application/vnd.debian.binary-package"),
+    ("rpm", "application/x-rpm"),
+    ("dmg", "application/x-apple-diskimage"),
+    ("iso", "application/x-iso9660-image"),
+    // Binaries
+    ("bin", "application/octet-stream"),
+    ("exe", "application/octet-stream"),
+    ("dll", "application/octet-stream"),
+    ("so", "application/octet-stream"),
+    ("apk", "application/vnd.android.package-archive"),
+    ("swf", "application/x-shockwave-flash"),
+];
+
+/// Table mapping file extensions to MIME types. Extensions are case-insensitive and may contain
+/// several dots, like `tar.gz`.
+#[derive(Debug, Clone)]
+pub struct MimeTypes {
+    types: HashMap<String, Mime>,
+}
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        let mut mime_types = MimeTypes::empty();
+        for (extension, mime) in DEFAULT_TYPES {
+            if let Ok(mime) = mime.parse() {
+                mime_types.insert(extension, mime);
+            }
+        }
+        mime_types
+    }
+}
+
+impl MimeTypes {
+    /// Creates a new [`MimeTypes`] containing the default table.
+    pub fn new() -> MimeTypes {
+        MimeTypes::default()
+    }
+
+    /// Creates a new [`MimeTypes`] without any extension.
+    pub fn empty() -> MimeTypes {
+        MimeTypes {
+            types: HashMap::new(),
+        }
+    }
+
+    /// Maps an extension, without its leading dot, to a MIME type. Replaces the previous type of
+    /// the extension if any.
+    pub fn insert(&mut self, extension: &str, mime: Mime) {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        self.types.insert(extension, mime);
+    }
+
+    /// Adds the mappings of a file using the format of `/etc/mime.types`: each line contains a
+    /// MIME type followed by its extensions, separated by whitespace. Lines starting with `#` are
+    /// comments. Lines with an invalid MIME type are ignored.
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let content = fs::read_to_string(path)?;
+        self.load_str(&content);
+        Ok(())
+    }
+
+    /// Adds the mappings of a string using the format of `/etc/mime.types`.
+    pub fn load_str(&mut self, content: &str) {
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let mime = match fields.next().map(str::parse::<Mime>) {
+                Some(Ok(mime)) => mime,
+                _ => continue,
+            };
+            for extension in fields {
+                self.insert(extension, mime.clone());
+            }
+        }
+    }
+
+    /// Returns the MIME type of a file based on its name. The longest known extension is used,
+    /// so that `archive.tar.gz` is found as `tar.gz` before `gz`. Returns `None` if no extension
+    /// of the file is known.
+    pub fn lookup(&self, filename: &str) -> Option<Mime> {
+        let name = filename
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(filename)
+            .to_ascii_lowercase();
+        // A leading dot marks a hidden file, not an extension
+        let name = name.strip_prefix('.').unwrap_or(&name);
+
+        name.match_indices('.')
+            .map(|(position, _)| &name[position + 1..])
+            .find_map(|extension| self.types.get(extension))
+            .cloned()
+    }
+}
+
+/// Returns the table of default MIME types, shared by all the callers.
+pub fn default_mime_types() -> &'static MimeTypes {
+    static DEFAULT: OnceLock<MimeTypes> = OnceLock::new();
+    DEFAULT.get_or_init(MimeTypes::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_common_types() {
+        let mime_types = MimeTypes::new();
+
+        assert_eq!(mime_types.lookup("style.css"), Some(mime::TEXT_CSS));
+        assert_eq!(mime_types.lookup("app.js"), Some(mime::TEXT_JAVASCRIPT));
+        assert_eq!(mime_types.lookup("logo.svg"), Some(mime::IMAGE_SVG));
+        assert_eq!(
+            mime_types
+                .lookup("module.wasm")
+                .map(|mime| mime.to_string()),
+            Some(String::from("application/wasm"))
+        );
+        assert_eq!(mime_types.lookup("doc.pdf"), Some(mime::APPLICATION_PDF));
+        assert_eq!(mime_types.lookup("font.woff2"), Some(mime::FONT_WOFF2));
+    }
+
+    #[test]
+    fn lookup_is_case_insensitive() {
+        let mime_types = MimeTypes::new();
+
+        assert_eq!(mime_types.lookup("PHOTO.JPG"), Some(mime::IMAGE_JPEG));
+        assert_eq!(mime_types.lookup("Index.Html"), Some(mime::TEXT_HTML));
+    }
+
+    #[test]
+    fn lookup_multi_dot_extension() {
+        let mut mime_types = MimeTypes::new();
+        mime_types.insert("min.js", mime::TEXT_PLAIN);
+
+        assert_eq!(
+            mime_types
+                .lookup("backup.2021.tar.gz")
+                .map(|mime| mime.to_string()),
+            Some(String::from("application/gzip"))
+        );
+        assert_eq!(mime_types.lookup("app.min.js"), Some(mime::TEXT_PLAIN));
+        assert_eq!(
+            mime_types.lookup("jquery-3.6.0.js"),
+            Some(mime::TEXT_JAVASCRIPT)
+        );
+    }
+
+    #[test]
+    fn lookup_unknown_extension() {
+        let mime_types = MimeTypes::new();
+
+        assert_eq!(mime_types.lookup("file.unknown"), None);
+        assert_eq!(mime_types.lookup("Makefile"), None);
+        assert_eq!(mime_types.lookup(".html"), None);
+        assert_eq!(mime_types.lookup("dir.html/file"), None);
+    }
+
+    #[test]
+    fn load_mime_types_file() {
+        let mut mime_types = MimeTypes::empty();
+
+        mime_types.load_str(
+            "# MIME type\t\textensions\n\
+             text/x-custom\t\tcustom cst  # comment\n\
+             application/json\tJSON\n\
+             not-a-type\t\tbad\n\
+             image/x-none\n",
+        );
+
+        assert_eq!(
+            mime_types.lookup("a.cst").map(|mime| mime.to_string()),
+            Some(String::from("text/x-custom"))
+        );
+        assert_eq!(mime_types.lookup("a.json"), Some(mime::APPLICATION_JSON));
+        assert_eq!(mime_types.lookup("a.bad"), None);
+    }
+}
@@ -1,6 +1,38 @@
+/// Writes one line per served request in Common, Combined or JSON format
+pub mod access_log;
+/// Decodes request bodies (Content-Length and chunked transfer coding)
+pub mod body;
+/// Negotiates content codings and compresses response bodies
+pub mod compression;
+/// Evaluates conditional requests with entity tags and modification dates
+pub mod conditional;
 /// Manages content (file loading, etc) and handle content types
 pub mod content;
+/// Resolves request targets to files below a document root
+pub mod files;
+/// Renders directory listings as HTML or JSON
+pub mod listing;
+/// Wraps request handling with middlewares
+pub mod middleware;
+/// Maps file extensions to MIME types
+pub mod mime_types;
+/// Byte-level parser for the request line and header fields
+pub mod parser;
+/// Parses Range headers and builds partial content
+pub mod range;
+/// Reads requests from a stream
+pub mod reader;
 /// Stores and build HTTP request
 pub mod request;
+/// Builds and serializes HTTP responses
+pub mod response;
+/// Dispatches requests to handlers by method and path
+pub mod router;
 /// Http server implementation
 pub mod server;
+/// Finds the type of content from its first bytes
+pub mod sniff;
+/// Serves the files of a document root
+pub mod static_files;
+/// Splits request targets into their components and decodes queries
+pub mod uri;
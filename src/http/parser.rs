@@ -0,0 +1,435 @@
+use crate::http::request::{HttpRequestError, HttpRequestErrorKind};
+use http::header::{HeaderName, HeaderValue};
+
+/// Request line as found in the input, without any copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLine<'a> {
+    pub method: &'a str,
+    pub target: &'a str,
+    pub version: &'a str,
+}
+
+/// Header field as found in the input, without any copy. Optional whitespace around the value
+/// is already removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderField<'a> {
+    pub name: &'a str,
+    pub value: &'a [u8],
+}
+
+impl<'a> HeaderField<'a> {
+    /// Converts the field into a name and a value which can be stored in a [`http::HeaderMap`].
+    pub fn to_header(&self) -> Result<(HeaderName, HeaderValue), HttpRequestError> {
+        let name = HeaderName::from_bytes(self.name.as_bytes()).map_err(|_| {
+            HttpRequestError::new(
+                HttpRequestErrorKind::InvalidHeaderName,
+                "Header name contains invalid characters",
+            )
+        })?;
+        let value = HeaderValue::from_bytes(self.value).map_err(|_| {
+            HttpRequestError::new(
+                HttpRequestErrorKind::InvalidHeaderValue,
+                "Header value contains invalid characters",
+            )
+        })?;
+        Ok((name, value))
+    }
+}
+
+/// Returns true if the byte is a `tchar` as defined by RFC 9110 section 5.6.2.
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Returns true if the string is a non-empty token as defined by RFC 9110 section 5.6.2.
+pub fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_token_byte)
+}
+
+/// Returns true if the byte is allowed in a field value: visible characters, obs-text, space and
+/// horizontal tab (RFC 9110 section 5.5).
+fn is_field_value_byte(byte: u8) -> bool {
+    byte == b'\t' || byte == b' ' || (0x21..=0x7e).contains(&byte) || byte >= 0x80
+}
+
+/// Returns true if the string is a scheme as defined by RFC 3986 section 3.1.
+fn is_scheme(s: &str) -> bool {
+    let mut bytes = s.bytes();
+    bytes.next().is_some_and(|byte| byte.is_ascii_alphabetic())
+        && bytes.all(|byte| byte.is_ascii_alphanumeric() || b"+-.".contains(&byte))
+}
+
+/// Converts bytes already checked to be ascii into a string.
+fn ascii_str(bytes: &[u8], error: fn() -> HttpRequestError) -> Result<&str, HttpRequestError> {
+    std::str::from_utf8(bytes).map_err(|_| error())
+}
+
+fn invalid_request_line() -> HttpRequestError {
+    HttpRequestError::new(
+        HttpRequestErrorKind::InvalidRequestLine,
+        "Request line is not of the form `method SP request-target SP HTTP-version`",
+    )
+}
+
+fn invalid_target() -> HttpRequestError {
+    HttpRequestError::new(
+        HttpRequestErrorKind::InvalidTarget,
+        "Request target is not valid",
+    )
+}
+
+/// Removes spaces and tabs at both ends of the slice.
+pub(crate) fn trim_whitespace(mut value: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = value {
+        value = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = value {
+        value = rest;
+    }
+    value
+}
+
+/// Returns the position right after the empty line which ends the head of a request, if present.
+pub fn find_head_end(data: &[u8]) -> Option<usize> {
+    data.iter()
+        .enumerate()
+        .filter(|(_, &byte)| byte == b'\n')
+        .find_map(|(index, _)| match &data[index + 1..] {
+            [b'\n', ..] => Some(index + 2),
+            [b'\r', b'\n', ..] => Some(index + 3),
+            _ => None,
+        })
+}
+
+/// Splits data into lines terminated by CRLF. A bare LF is also accepted as line terminator as
+/// allowed by RFC 9112 section 2.2. A last line without terminator is returned as is.
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    data.split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+/// Checks that the request target matches one of the forms of RFC 9112 section 3.2 and that the
+/// form is allowed for the method.
+fn check_target_form(method: &str, target: &str) -> Result<(), HttpRequestError> {
+    let valid = if method == "CONNECT" {
+        // authority-form: uri-host ":" port
+        match target.rsplit_once(':') {
+            Some((host, port)) => {
+                !host.is_empty()
+                    && !host.contains(['/', '?', '#', '@'])
+                    && !port.is_empty()
+                    && port.bytes().all(|byte| byte.is_ascii_digit())
+            }
+            None => false,
+        }
+    } else if target == "*" {
+        method == "OPTIONS"
+    } else if target.starts_with('/') {
+        true
+    } else {
+        // absolute-form: scheme ":" hier-part
+        target
+            .split_once(':')
+            .is_some_and(|(scheme, _)| is_scheme(scheme))
+    };
+
+    if !valid || target.contains('#') {
+        return Err(invalid_target());
+    }
+    Ok(())
+}
+
+/// Parses a request line, without its line terminator, following the grammar
+/// `method SP request-target SP HTTP-version` of RFC 9112 section 3.
+/// Spaces after the version are ignored. The RFC does not allow them, this leniency is kept for
+/// compatibility with the request lines accepted by the original parser, e.g.
+/// `GET /index.html HTTP/1.1 `. The version is only checked to be made of visible characters,
+/// checking that it is supported is left to the caller.
+/// A line without version is an HTTP/0.9 simple request, which is rejected as an unsupported
+/// version: the simple response expected by such clients has no status line nor headers.
+pub fn parse_request_line(line: &[u8]) -> Result<RequestLine<'_>, HttpRequestError> {
+    let line = match line {
+        [b' ' | b'\t', ..] | [] => return Err(invalid_request_line()),
+        _ => {
+            let end = line.len() - line.iter().rev().take_while(|&&byte| byte == b' ').count();
+            &line[..end]
+        }
+    };
+
+    let mut parts = line.splitn(3, |&byte| byte == b' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        (Some(b"GET"), Some([b'/', ..]), None) => {
+            return Err(HttpRequestError::new(
+                HttpRequestErrorKind::UnsupportedVersion,
+                "HTTP/0.9 simple requests are not supported",
+            ))
+        }
+        _ => return Err(invalid_request_line()),
+    };
+
+    if method.is_empty() || !method.iter().all(|&byte| is_token_byte(byte)) {
+        return Err(HttpRequestError::new(
+            HttpRequestErrorKind::InvalidMethod,
+            "Method is not a valid token",
+        ));
+    }
+
+    if target.is_empty() || !target.iter().all(|byte| (0x21..=0x7e).contains(byte)) {
+        return Err(invalid_target());
+    }
+
+    if version.is_empty() || !version.iter().all(|byte| (0x21..=0x7e).contains(byte)) {
+        return Err(invalid_request_line());
+    }
+
+    let method = ascii_str(method, invalid_request_line)?;
+    let target = ascii_str(target, invalid_target)?;
+    let version = ascii_str(version, invalid_request_line)?;
+    check_target_form(method, target)?;
+
+    Ok(RequestLine {
+        method,
+        target,
+        version,
+    })
+}
+
+/// Parses a header field line, without its line terminator, following the grammar
+/// `field-name ":" OWS field-value OWS` of RFC 9112 section 5.
+/// Whitespace between the field name and the colon and obsolete line folding are rejected.
+pub fn parse_header_field(line: &[u8]) -> Result<HeaderField<'_>, HttpRequestError> {
+    if let [b' ' | b'\t', ..] = line {
+        return Err(HttpRequestError::new(
+            HttpRequestErrorKind::MalformedHeader,
+            "Obsolete line folding is not supported",
+        ));
+    }
+
+    let colon = line.iter().position(|&byte| byte == b':').ok_or_else(|| {
+        HttpRequestError::new(
+            HttpRequestErrorKind::MalformedHeader,
+            "Header line does not contain a colon",
+        )
+    })?;
+    let (name, value) = (&line[..colon], &line[colon + 1..]);
+
+    if name.is_empty() || !name.iter().all(|&byte| is_token_byte(byte)) {
+        return Err(HttpRequestError::new(
+            HttpRequestErrorKind::InvalidHeaderName,
+            "Header name is not a valid token",
+        ));
+    }
+
+    let value = trim_whitespace(value);
+    if !value.iter().all(|&byte| is_field_value_byte(byte)) {
+        return Err(HttpRequestError::new(
+            HttpRequestErrorKind::InvalidHeaderValue,
+            "Header value contains invalid characters",
+        ));
+    }
+
+    Ok(HeaderField {
+        name: ascii_str(name, || {
+            HttpRequestError::new(
+                HttpRequestErrorKind::InvalidHeaderName,
+                "Header name is not a valid token",
+            )
+        })?,
+        value,
+    })
+}
+
+/// Parses the head of a request: the request line followed by header fields until an empty line
+/// or the end of input. Data after the empty line is ignored.
+pub fn parse_head(
+    data: &[u8],
+) -> Result<(RequestLine<'_>, Vec<HeaderField<'_>>), HttpRequestError> {
+    let mut lines = lines(data);
+    let line = parse_request_line(lines.next().unwrap_or(b""))?;
+
+    let fields = lines
+        .take_while(|line| !line.is_empty())
+        .map(parse_header_field)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((line, fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn kind_of<T>(result: Result<T, HttpRequestError>) -> Option<HttpRequestErrorKind> {
+        result.err().map(|error| error.kind())
+    }
+
+    #[test]
+    fn parse_simple_request_line() {
+        let result = parse_request_line(b"GET /index.html HTTP/1.1").expect("");
+
+        assert_eq!(
+            result,
+            RequestLine {
+                method: "GET",
+                target: "/index.html",
+                version: "HTTP/1.1",
+            }
+        );
+    }
+
+    #[test]
+    fn reject_malformed_request_lines() {
+        let lines: [&[u8]; 6] = [
+            b"",
+            b" GET / HTTP/1.1",
+            b"GET  / HTTP/1.1",
+            b"GET / HTTP/1.1 extra",
+            b"GET /\r HTTP/1.1",
+            b"GET /",
+        ];
+
+        for line in lines.iter() {
+            assert!(parse_request_line(line).is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn reject_http_09_simple_request() {
+        let result = parse_request_line(b"GET /index.html");
+
+        assert_eq!(
+            kind_of(result),
+            Some(HttpRequestErrorKind::UnsupportedVersion)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_method() {
+        let result = parse_request_line(b"G(T / HTTP/1.1");
+
+        assert_eq!(kind_of(result), Some(HttpRequestErrorKind::InvalidMethod));
+    }
+
+    #[test]
+    fn reject_target_with_spaces_or_controls() {
+        let result = parse_request_line(b"GET /a b HTTP/1.1");
+        assert_eq!(
+            kind_of(result),
+            Some(HttpRequestErrorKind::InvalidRequestLine)
+        );
+
+        let result = parse_request_line(b"GET /a\x7fb HTTP/1.1");
+        assert_eq!(kind_of(result), Some(HttpRequestErrorKind::InvalidTarget));
+    }
+
+    #[test]
+    fn check_request_target_forms() {
+        assert!(parse_request_line(b"GET http://localhost/a HTTP/1.1").is_ok());
+        assert!(parse_request_line(b"CONNECT localhost:443 HTTP/1.1").is_ok());
+        assert!(parse_request_line(b"OPTIONS * HTTP/1.1").is_ok());
+
+        let targets: [&[u8]; 4] = [
+            b"GET * HTTP/1.1",
+            b"CONNECT /a HTTP/1.1",
+            b"GET index.html HTTP/1.1",
+            b"GET /a#fragment HTTP/1.1",
+        ];
+        for target in targets.iter() {
+            assert_eq!(
+                kind_of(parse_request_line(target)),
+                Some(HttpRequestErrorKind::InvalidTarget),
+                "{:?}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn parse_header_field_with_obs_text() {
+        let result = parse_header_field(b"X-Name:  caf\xe9 \t").expect("");
+
+        assert_eq!(result.name, "X-Name");
+        assert_eq!(result.value, b"caf\xe9");
+    }
+
+    #[test]
+    fn reject_header_with_bare_carriage_return() {
+        let result = parse_header_field(b"X-Name: a\rb");
+
+        assert_eq!(
+            kind_of(result),
+            Some(HttpRequestErrorKind::InvalidHeaderValue)
+        );
+    }
+
+    #[test]
+    fn parse_head_with_mixed_line_endings() {
+        let (line, fields) =
+            parse_head(b"GET / HTTP/1.1\r\nHost: a\nAccept: */*\r\n\r\nbody").expect("");
+
+        assert_eq!(line.target, "/");
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].value, b"*/*");
+    }
+
+    /// Small xorshift generator so the mutations are the same on every run
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+    }
+
+    /// Checks properties which must hold for any input accepted by the parser.
+    fn check_parsed_head(data: &[u8]) {
+        if let Ok((line, fields)) = parse_head(data) {
+            assert!(is_token(line.method));
+            assert!(!line.target.is_empty());
+            assert!(!line.target.bytes().any(|byte| byte <= b' ' || byte == 0x7f));
+            for field in fields {
+                assert!(is_token(field.name));
+                assert!(!field
+                    .value
+                    .iter()
+                    .any(|&byte| byte == b'\r' || byte == b'\n'));
+                assert!(field.to_header().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn parse_mutated_fuzz_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/parse_request");
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let interesting = b" \t\r\n:/*?#%\x00\x7f\xff";
+
+        for entry in fs::read_dir(corpus).expect("Fuzz corpus not found") {
+            let seed = fs::read(entry.expect("").path()).expect("");
+            check_parsed_head(&seed);
+
+            for _ in 0..2000 {
+                let mut data = seed.clone();
+                for _ in 0..1 + random.next() % 4 {
+                    let position = random.next() % (data.len() + 1);
+                    match random.next() % 4 {
+                        0 if position < data.len() => data[position] ^= 1 << (random.next() % 8),
+                        1 if position < data.len() => {
+                            data.remove(position);
+                        }
+                        2 => data.insert(position, interesting[random.next() % interesting.len()]),
+                        _ => data.truncate(position),
+                    }
+                }
+                check_parsed_head(&data);
+            }
+        }
+    }
+}
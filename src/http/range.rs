@@ -0,0 +1,359 @@
+use crate::http::response::Body;
+use http::header::HeaderValue;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maximum number of ranges served in a single response. Requests for more ranges, once
+/// overlapping ones are merged, are answered with the whole representation.
+pub const MAX_RANGES: usize = 16;
+
+/// Range of bytes of a representation. Both positions are inclusive, as in `Content-Range`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+impl ByteRange {
+    /// Returns the number of bytes of the range.
+    // A range contains at least one byte, so it cannot be empty
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.last - self.first + 1
+    }
+
+    /// Returns the value of the `Content-Range` header of this range, in a representation of
+    /// `complete_length` bytes.
+    pub fn content_range(&self, complete_length: u64) -> HeaderValue {
+        HeaderValue::from(ContentRange::Range(*self, complete_length))
+    }
+}
+
+/// Value of a `Content-Range` header, as described in RFC 9110 section 14.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContentRange {
+    Range(ByteRange, u64),
+    Unsatisfied(u64),
+}
+
+impl From<ContentRange> for HeaderValue {
+    fn from(content_range: ContentRange) -> HeaderValue {
+        let value = match content_range {
+            ContentRange::Range(range, length) => {
+                format!("bytes {}-{}/{}", range.first, range.last, length)
+            }
+            ContentRange::Unsatisfied(length) => format!("bytes */{}", length),
+        };
+        // Only contains digits and ASCII characters
+        HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static("bytes */0"))
+    }
+}
+
+/// Returns the value of the `Content-Range` header of a 416 response, for a representation of
+/// `complete_length` bytes.
+pub fn unsatisfied_content_range(complete_length: u64) -> HeaderValue {
+    HeaderValue::from(ContentRange::Unsatisfied(complete_length))
+}
+
+/// Result of the evaluation of a `Range` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// Header is invalid, uses another unit or asks for too many ranges: the whole
+    /// representation must be sent
+    Ignored,
+    /// Ranges to send, sorted and without overlap
+    Satisfiable(Vec<ByteRange>),
+    /// None of the ranges overlaps the representation, answer with 416
+    Unsatisfiable,
+}
+
+/// Parses a single range specification, e.g. `0-499`, `500-` or `-200`. Returns `Err` if the
+/// syntax is invalid and `Ok(None)` if the range does not overlap the representation.
+fn parse_range_spec(spec: &str, length: u64) -> Result<Option<ByteRange>, ()> {
+    let (first, last) = spec.split_once('-').ok_or(())?;
+    let parse = |value: &str| {
+        if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(());
+        }
+        // Positions larger than u64 are beyond any representation
+        Ok(value.parse::<u64>().unwrap_or(u64::MAX))
+    };
+
+    if first.is_empty() {
+        // Suffix range: last bytes of the representation
+        let suffix = parse(last)?;
+        if suffix == 0 || length == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(ByteRange {
+            first: length.saturating_sub(suffix),
+            last: length - 1,
+        }));
+    }
+
+    let first = parse(first)?;
+    let last = if last.is_empty() {
+        u64::MAX
+    } else {
+        parse(last)?
+    };
+    if last < first {
+        return Err(());
+    }
+    if first >= length {
+        return Ok(None);
+    }
+    Ok(Some(ByteRange {
+        first,
+        last: last.min(length - 1),
+    }))
+}
+
+/// Evaluates a `Range` header against a representation of `length` bytes, following RFC 9110
+/// section 14.2. Overlapping and adjacent ranges are merged.
+pub fn parse_range(value: &str, length: u64) -> RangeRequest {
+    let specs = match value.trim().split_once('=') {
+        Some((unit, specs)) if unit.trim().eq_ignore_ascii_case("bytes") => specs,
+        _ => return RangeRequest::Ignored,
+    };
+
+    let mut ranges = Vec::new();
+    let mut is_empty = true;
+    for spec in specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+    {
+        is_empty = false;
+        match parse_range_spec(spec, length) {
+            Ok(Some(range)) => ranges.push(range),
+            Ok(None) => (),
+            Err(()) => return RangeRequest::Ignored,
+        }
+    }
+    if is_empty {
+        return RangeRequest::Ignored;
+    }
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    ranges.sort_by_key(|range| range.first);
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(previous) if range.first <= previous.last.saturating_add(1) => {
+                previous.last = previous.last.max(range.last)
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    if merged.len() > MAX_RANGES {
+        return RangeRequest::Ignored;
+    }
+    RangeRequest::Satisfiable(merged)
+}
+
+/// Generates a boundary separating the parts of a `multipart/byteranges` body. Boundaries only
+/// have to be absent from the content, which is very unlikely for a unique random-looking value.
+pub fn generate_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    // Mixes the values so that consecutive boundaries do not look alike
+    let mixed = (nanos ^ count.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    format!("{:016x}{:016x}", mixed, nanos)
+}
+
+/// Returns the header of a part of a `multipart/byteranges` body, starting with the delimiter.
+fn part_header(
+    boundary: &str,
+    content_type: &HeaderValue,
+    range: &ByteRange,
+    complete_length: u64,
+) -> Vec<u8> {
+    let mut header = format!("\r\n--{}\r\n", boundary).into_bytes();
+    header.extend_from_slice(b"Content-Type: ");
+    header.extend_from_slice(content_type.as_bytes());
+    header.extend_from_slice(b"\r\nContent-Range: ");
+    header.extend_from_slice(range.content_range(complete_length).as_bytes());
+    header.extend_from_slice(b"\r\n\r\n");
+    header
+}
+
+/// Segment of a `multipart/byteranges` body
+enum Segment {
+    /// Delimiter and header of a part, or closing delimiter
+    Bytes(io::Cursor<Vec<u8>>),
+    /// Range of the content, with the number of bytes already read
+    Range(ByteRange, u64),
+}
+
+/// Reader producing a `multipart/byteranges` body from the ranges of some content. Ranges are
+/// read one after the other, so only a single buffer is used whatever the number of ranges.
+struct MultipartReader<R: Read + Seek> {
+    content: R,
+    segments: VecDeque<Segment>,
+}
+
+impl<R: Read + Seek> Read for MultipartReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(segment) = self.segments.front_mut() {
+            let read = match segment {
+                Segment::Bytes(bytes) => bytes.read(buf)?,
+                Segment::Range(range, done) => {
+                    if *done == 0 {
+                        self.content.seek(SeekFrom::Start(range.first))?;
+                    }
+                    let remaining = range.len() - *done;
+                    let limit = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+                    let read = self.content.read(&mut buf[..limit])?;
+                    if read == 0 && remaining > 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "Content ended before the end of a range",
+                        ));
+                    }
+                    *done += read as u64;
+                    read
+                }
+            };
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            self.segments.pop_front();
+        }
+        Ok(0)
+    }
+}
+
+/// Builds a `multipart/byteranges` body streaming the ranges of some content of
+/// `complete_length` bytes. The length of the body is computed in advance.
+pub fn multipart_body<R: Read + Seek + Send + 'static>(
+    content: R,
+    ranges: &[ByteRange],
+    content_type: &HeaderValue,
+    boundary: &str,
+    complete_length: u64,
+) -> Body {
+    let mut segments = VecDeque::with_capacity(ranges.len() * 2 + 1);
+    let mut length = 0;
+    for range in ranges {
+        let header = part_header(boundary, content_type, range, complete_length);
+        length += header.len() as u64 + range.len();
+        segments.push_back(Segment::Bytes(io::Cursor::new(header)));
+        segments.push_back(Segment::Range(*range, 0));
+    }
+    let closing = format!("\r\n--{}--\r\n", boundary).into_bytes();
+    length += closing.len() as u64;
+    segments.push_back(Segment::Bytes(io::Cursor::new(closing)));
+
+    Body::Stream {
+        reader: Box::new(MultipartReader { content, segments }),
+        length: Some(length),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(first: u64, last: u64) -> ByteRange {
+        ByteRange { first, last }
+    }
+
+    #[test]
+    fn parse_single_ranges() {
+        assert_eq!(
+            parse_range("bytes=0-499", 1000),
+            RangeRequest::Satisfiable(vec![range(0, 499)])
+        );
+        assert_eq!(
+            parse_range("bytes=500-", 1000),
+            RangeRequest::Satisfiable(vec![range(500, 999)])
+        );
+        assert_eq!(
+            parse_range("bytes=-200", 1000),
+            RangeRequest::Satisfiable(vec![range(800, 999)])
+        );
+        assert_eq!(
+            parse_range("bytes=-2000", 1000),
+            RangeRequest::Satisfiable(vec![range(0, 999)])
+        );
+        assert_eq!(
+            parse_range("bytes=900-99999999999999999999999", 1000),
+            RangeRequest::Satisfiable(vec![range(900, 999)])
+        );
+    }
+
+    #[test]
+    fn merge_overlapping_ranges() {
+        assert_eq!(
+            parse_range("bytes=500-600, 0-99, 601-700,50-150", 1000),
+            RangeRequest::Satisfiable(vec![range(0, 150), range(500, 700)])
+        );
+    }
+
+    #[test]
+    fn ignore_invalid_ranges() {
+        assert_eq!(parse_range("items=0-1", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("bytes=", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("bytes=5-1", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("bytes=a-b", 1000), RangeRequest::Ignored);
+        assert_eq!(parse_range("bytes=0-1,x", 1000), RangeRequest::Ignored);
+        let many: Vec<String> = (0..20).map(|i| format!("{}-{}", i * 10, i * 10)).collect();
+        assert_eq!(
+            parse_range(&format!("bytes={}", many.join(",")), 1000),
+            RangeRequest::Ignored
+        );
+    }
+
+    #[test]
+    fn detect_unsatisfiable_ranges() {
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-5", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(
+            parse_range("bytes=2000-3000, 10-19", 1000),
+            RangeRequest::Satisfiable(vec![range(10, 19)])
+        );
+        assert_eq!(unsatisfied_content_range(1000), "bytes */1000");
+    }
+
+    #[test]
+    fn build_multipart_body() {
+        let content_type = HeaderValue::from_static("text/plain");
+
+        let body = multipart_body(
+            io::Cursor::new(b"0123456789".to_vec()),
+            &[range(0, 1), range(8, 9)],
+            &content_type,
+            "XYZ",
+            10,
+        );
+        let (mut reader, length) = match body {
+            Body::Stream { reader, length } => (reader, length),
+            other => panic!("Unexpected body {:?}", other),
+        };
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).expect("");
+
+        assert_eq!(
+            String::from_utf8(bytes.clone()).expect(""),
+            "\r\n--XYZ\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\
+             \r\n--XYZ\r\nContent-Type: text/plain\r\nContent-Range: bytes 8-9/10\r\n\r\n89\
+             \r\n--XYZ--\r\n"
+        );
+        assert_eq!(length, Some(bytes.len() as u64));
+        assert_ne!(generate_boundary(), generate_boundary());
+    }
+}
@@ -0,0 +1,452 @@
+use crate::http::body::{BodyDecoder, BodyFraming};
+use crate::http::parser::{find_head_end, parse_request_line};
+use crate::http::request::{
+    parse_head, HttpRequest, HttpRequestError, HttpRequestErrorKind, HttpVersion,
+};
+use http::header::EXPECT;
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+
+/// Size of the buffer used for each read on the stream
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Limits enforced while reading a request
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    /// Maximum size in bytes of the request line and header fields. Larger heads are rejected with 431.
+    pub max_header_size: usize,
+    /// Maximum number of header fields. Requests with more fields are rejected with 431.
+    pub max_headers: usize,
+    /// Maximum size in bytes of a request body. Larger requests are rejected with 413.
+    pub max_body_size: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            max_header_size: 8 * 1024,
+            max_headers: 100,
+            max_body_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Error occurring while reading a request from a stream
+#[derive(Debug)]
+pub enum ReadError {
+    /// Stream could not be read
+    Io(io::Error),
+    /// Received data is not a valid request
+    Http(HttpRequestError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(error) => write!(f, "{}", error),
+            ReadError::Http(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+impl From<HttpRequestError> for ReadError {
+    fn from(error: HttpRequestError) -> Self {
+        ReadError::Http(error)
+    }
+}
+
+/// Reads HTTP requests one at a time from a stream.
+/// Data received after the end of a request is kept for the next call to
+/// [`RequestReader::read_request`].
+pub struct RequestReader<S> {
+    stream: S,
+    buffer: Vec<u8>,
+    limits: RequestLimits,
+}
+
+impl<S: Read + Write> RequestReader<S> {
+    /// Creates a new [`RequestReader`] reading from the provided stream.
+    pub fn new(stream: S, limits: RequestLimits) -> RequestReader<S> {
+        RequestReader {
+            stream,
+            buffer: Vec::new(),
+            limits,
+        }
+    }
+
+    /// Returns the underlying stream, used to write responses.
+    pub fn stream_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the version of the request being read, if its request line is received and valid.
+    /// Used to answer a request which cannot be read in a version supported by the client.
+    pub fn request_version(&self) -> Option<HttpVersion> {
+        let end = self.buffer.iter().position(|&byte| byte == b'\n')?;
+        let line = &self.buffer[..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        parse_request_line(line)
+            .ok()
+            .and_then(|line| line.version.parse().ok())
+    }
+
+    /// Reads the next complete request, including its body, and parses exactly the bytes which
+    /// form it. Returns `None` if the peer closed the stream before starting a new request.
+    pub fn read_request(&mut self) -> Result<Option<HttpRequest>, ReadError> {
+        let head_end = match self.read_head()? {
+            Some(head_end) => head_end,
+            None => return Ok(None),
+        };
+
+        let (line, headers) = parse_head(&self.buffer[..head_end])?;
+        let framing = BodyFraming::from_headers(&headers)?;
+        // Interim responses must not be sent to HTTP/1.0 clients (RFC 9110 section 10.1.1)
+        let expects_continue = line.version == HttpVersion::V11
+            && headers
+                .get(EXPECT)
+                .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+        let mut continue_sent = false;
+        let mut decoder = BodyDecoder::new(framing, &self.limits);
+
+        loop {
+            if let Some(body) = decoder.decode(&self.buffer[head_end..])? {
+                self.buffer.drain(..head_end + body.length);
+                return Ok(Some(HttpRequest {
+                    line,
+                    headers,
+                    body: body.data,
+                    trailers: body.trailers,
+                }));
+            }
+
+            // Client waits for an interim response before sending the body
+            if expects_continue && !continue_sent {
+                self.stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                self.stream.flush()?;
+                continue_sent = true;
+            }
+
+            // Chunked coding may make the received body larger than the decoded one, but not
+            // without bound
+            if self.buffer.len() - head_end > self.max_encoded_body_size() {
+                return Err(ReadError::Http(HttpRequestError::new(
+                    HttpRequestErrorKind::BodyTooLarge,
+                    "Request body exceeds the maximum allowed size",
+                )));
+            }
+
+            if self.fill_buffer()? == 0 {
+                return Err(ReadError::Http(HttpRequestError::new(
+                    HttpRequestErrorKind::IncompleteBody,
+                    "Connection closed before the end of the request body",
+                )));
+            }
+        }
+    }
+
+    /// Returns the maximum number of bytes received for a body: its maximum size, plus a quarter
+    /// of it for the chunked coding and a trailer section as large as a request head.
+    fn max_encoded_body_size(&self) -> usize {
+        let limits = &self.limits;
+        limits
+            .max_body_size
+            .saturating_add(limits.max_body_size / 4)
+            .saturating_add(limits.max_header_size)
+    }
+
+    /// Reads until the empty line ending the request head is received and returns its position.
+    /// Returns `None` if the stream is closed before any byte of a new request was received.
+    fn read_head(&mut self) -> Result<Option<usize>, ReadError> {
+        let mut searched: usize = 0;
+
+        loop {
+            // Empty lines received before the request line must be ignored (RFC 9112 section 2.2)
+            let blank = self
+                .buffer
+                .iter()
+                .take_while(|&&byte| byte == b'\r' || byte == b'\n')
+                .count();
+            self.buffer.drain(..blank);
+            searched = searched.saturating_sub(blank);
+
+            // Terminator may straddle two reads, so a few already searched bytes are searched again
+            let start = searched.saturating_sub(3);
+            if let Some(head_end) = find_head_end(&self.buffer[start..]) {
+                let head_end = start + head_end;
+                if head_end > self.limits.max_header_size {
+                    return Err(headers_too_large());
+                }
+                self.check_header_count(head_end)?;
+                return Ok(Some(head_end));
+            }
+            searched = self.buffer.len();
+
+            if self.buffer.len() > self.limits.max_header_size {
+                return Err(headers_too_large());
+            }
+
+            if self.fill_buffer()? == 0 {
+                return if self.buffer.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(ReadError::Http(HttpRequestError::new(
+                        HttpRequestErrorKind::IncompleteHead,
+                        "Connection closed before the end of the request head",
+                    )))
+                };
+            }
+        }
+    }
+
+    /// Checks that the head ending at `head_end` does not contain too many header fields.
+    fn check_header_count(&self, head_end: usize) -> Result<(), ReadError> {
+        // Head contains the request line, the header lines and the final empty line
+        let lines = self.buffer[..head_end]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count();
+
+        if lines.saturating_sub(2) > self.limits.max_headers {
+            return Err(ReadError::Http(HttpRequestError::new(
+                HttpRequestErrorKind::TooManyHeaders,
+                "Request contains too many header fields",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Performs a single read on the stream and appends the received bytes to the buffer.
+    /// Returns the number of bytes received, zero meaning that the stream was closed.
+    fn fill_buffer(&mut self) -> io::Result<usize> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(size) => {
+                    self.buffer.extend_from_slice(&chunk[..size]);
+                    return Ok(size);
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+fn headers_too_large() -> ReadError {
+    ReadError::Http(HttpRequestError::new(
+        HttpRequestErrorKind::HeadersTooLarge,
+        "Request head exceeds the maximum allowed size",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::StatusCode;
+
+    /// Stream returning its input in chunks of a few bytes
+    struct TestStream {
+        input_data: Vec<u8>,
+        output_data: Vec<u8>,
+        chunk_size: usize,
+    }
+
+    impl TestStream {
+        fn new(input: &[u8], chunk_size: usize) -> TestStream {
+            TestStream {
+                input_data: input.to_vec(),
+                output_data: vec![],
+                chunk_size,
+            }
+        }
+    }
+
+    impl Write for TestStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output_data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for TestStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let size = buf.len().min(self.chunk_size).min(self.input_data.len());
+            buf[..size].copy_from_slice(&self.input_data[..size]);
+            self.input_data.drain(..size);
+            Ok(size)
+        }
+    }
+
+    fn status_of(result: Result<Option<HttpRequest>, ReadError>) -> Option<StatusCode> {
+        match result {
+            Err(ReadError::Http(error)) => Some(error.status_code()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn read_request_split_in_small_chunks() {
+        let input = b"GET /hello.html HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n";
+        let mut reader = RequestReader::new(TestStream::new(input, 3), RequestLimits::default());
+
+        let result = reader.read_request().expect("").expect("");
+
+        assert_eq!(result.line.uri, "/hello.html");
+        assert_eq!(result.headers["host"], "localhost");
+        assert_eq!(result.headers["accept"], "*/*");
+    }
+
+    #[test]
+    fn read_request_larger_than_one_read() {
+        let mut input = b"GET / HTTP/1.1\r\n".to_vec();
+        input.extend_from_slice(format!("X-Long: {}\r\n\r\n", "a".repeat(6000)).as_bytes());
+        let mut reader =
+            RequestReader::new(TestStream::new(&input, 1000), RequestLimits::default());
+
+        let result = reader.read_request().expect("").expect("");
+
+        assert_eq!(result.headers["x-long"].len(), 6000);
+    }
+
+    #[test]
+    fn read_request_with_body_in_small_chunks() {
+        let input = b"GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        let mut reader = RequestReader::new(TestStream::new(input, 2), RequestLimits::default());
+
+        let result = reader.read_request().expect("").expect("");
+
+        assert_eq!(result.body, b"hello".to_vec());
+    }
+
+    #[test]
+    fn read_consecutive_requests() {
+        let input = b"GET /a HTTP/1.1\r\n\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+        let mut reader = RequestReader::new(TestStream::new(input, 64), RequestLimits::default());
+
+        let first = reader.read_request().expect("").expect("");
+        let second = reader.read_request().expect("").expect("");
+        let end = reader.read_request().expect("");
+
+        assert_eq!(first.line.uri, "/a");
+        assert_eq!(second.line.uri, "/b");
+        assert!(end.is_none());
+    }
+
+    #[test]
+    fn read_closed_stream() {
+        let mut reader = RequestReader::new(TestStream::new(b"", 8), RequestLimits::default());
+
+        assert!(matches!(reader.read_request(), Ok(None)));
+    }
+
+    #[test]
+    fn read_truncated_head() {
+        let mut reader = RequestReader::new(
+            TestStream::new(b"GET / HTTP/1.1\r\nHo", 8),
+            RequestLimits::default(),
+        );
+
+        assert_eq!(
+            status_of(reader.read_request()),
+            Some(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn reject_too_large_head() {
+        let input = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(100));
+        let limits = RequestLimits {
+            max_header_size: 64,
+            ..RequestLimits::default()
+        };
+        let mut reader = RequestReader::new(TestStream::new(input.as_bytes(), 16), limits);
+
+        assert_eq!(
+            status_of(reader.read_request()),
+            Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn reject_too_many_headers() {
+        let input = b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n";
+        let limits = RequestLimits {
+            max_headers: 2,
+            ..RequestLimits::default()
+        };
+        let mut reader = RequestReader::new(TestStream::new(input, 16), limits);
+
+        assert_eq!(
+            status_of(reader.read_request()),
+            Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn reject_body_with_too_much_framing() {
+        let mut input = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        input.extend_from_slice(&b"1\r\na\r\n".repeat(1000));
+        let limits = RequestLimits {
+            max_header_size: 64,
+            max_body_size: 1000,
+            ..RequestLimits::default()
+        };
+        let mut reader = RequestReader::new(TestStream::new(&input, 16), limits);
+
+        assert_eq!(
+            status_of(reader.read_request()),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn no_continue_for_10_client() {
+        let input = b"POST / HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nhello";
+        let mut stream = TestStream::new(input, input.len() - 5);
+
+        let result = RequestReader::new(&mut stream, RequestLimits::default())
+            .read_request()
+            .expect("")
+            .expect("");
+
+        assert_eq!(result.body, b"hello".to_vec());
+        assert!(stream.output_data.is_empty());
+    }
+
+    #[test]
+    fn find_version_of_invalid_request() {
+        let input = b"GET / HTTP/1.0\r\nBad\r\n\r\n";
+        let mut reader = RequestReader::new(TestStream::new(input, 64), RequestLimits::default());
+
+        assert!(reader.read_request().is_err());
+        assert_eq!(reader.request_version(), Some(HttpVersion::V10));
+    }
+
+    #[test]
+    fn send_continue_before_reading_body() {
+        let input = b"GET / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nhello";
+        let mut stream = TestStream::new(input, input.len() - 5);
+
+        let result = RequestReader::new(&mut stream, RequestLimits::default())
+            .read_request()
+            .expect("")
+            .expect("");
+
+        assert_eq!(result.body, b"hello".to_vec());
+        assert_eq!(
+            stream.output_data,
+            b"HTTP/1.1 100 Continue\r\n\r\n".to_vec()
+        );
+    }
+}
@@ -1,20 +1,89 @@
-use regex::Regex;
+use crate::http::body::{decode_body, BodyFraming};
+use crate::http::parser;
+use crate::http::parser::{find_head_end, is_token, RequestLine};
+use crate::http::uri::{QueryParams, RequestTarget};
+use http::header::{HeaderMap, CONNECTION, HOST};
+use http::StatusCode;
 use std::fmt;
 use std::str::FromStr;
 
+/// Kind of failure encountered when parsing an HTTP request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpRequestErrorKind {
+    /// Request line does not match `method SP request-target SP HTTP-version`
+    InvalidRequestLine,
+    /// Method is not a valid token
+    InvalidMethod,
+    /// Request target contains invalid characters or does not match the allowed forms
+    InvalidTarget,
+    /// Version is not of the form `HTTP/x.y`
+    InvalidVersion,
+    /// Major version is not supported by the server
+    UnsupportedVersion,
+    /// Header line is not of the form `field-name ":" OWS field-value OWS`
+    MalformedHeader,
+    /// Header field name is not a valid token
+    InvalidHeaderName,
+    /// Header field value contains forbidden characters
+    InvalidHeaderValue,
+    /// Body framing is invalid or chunked body is malformed
+    InvalidBody,
+    /// Request head is not terminated by an empty line
+    IncompleteHead,
+    /// Body is shorter than announced by the framing headers
+    IncompleteBody,
+    /// Request head is larger than the maximum size accepted by the server
+    HeadersTooLarge,
+    /// Request contains more header fields than accepted by the server
+    TooManyHeaders,
+    /// Transfer coding other than chunked was requested
+    UnsupportedTransferCoding,
+    /// Body is larger than the maximum size accepted by the server
+    BodyTooLarge,
+}
+
 /// Error returned when HTTP request parsing fails
 #[derive(Debug, Clone)]
 pub struct HttpRequestError {
+    kind: HttpRequestErrorKind,
     msg: String,
 }
 
 impl HttpRequestError {
     /// Creates a new [`HttpRequestError`]. An error message should be provided when building the error.
-    fn new(msg: &str) -> HttpRequestError {
+    pub(crate) fn new(kind: HttpRequestErrorKind, msg: &str) -> HttpRequestError {
         HttpRequestError {
+            kind,
             msg: String::from(msg),
         }
     }
+
+    /// Returns the kind of failure which caused the error.
+    pub fn kind(&self) -> HttpRequestErrorKind {
+        self.kind
+    }
+
+    /// Returns the status code of the response which should be sent back for this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self.kind {
+            HttpRequestErrorKind::UnsupportedTransferCoding => StatusCode::NOT_IMPLEMENTED,
+            HttpRequestErrorKind::UnsupportedVersion => StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+            HttpRequestErrorKind::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            HttpRequestErrorKind::HeadersTooLarge | HttpRequestErrorKind::TooManyHeaders => {
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+            }
+            HttpRequestErrorKind::InvalidRequestLine
+            | HttpRequestErrorKind::InvalidMethod
+            | HttpRequestErrorKind::InvalidTarget
+            | HttpRequestErrorKind::InvalidVersion
+            | HttpRequestErrorKind::MalformedHeader
+            | HttpRequestErrorKind::InvalidHeaderName
+            | HttpRequestErrorKind::InvalidHeaderValue
+            | HttpRequestErrorKind::InvalidBody
+            | HttpRequestErrorKind::IncompleteHead
+            | HttpRequestErrorKind::IncompleteBody => StatusCode::BAD_REQUEST,
+        }
+    }
 }
 
 impl fmt::Display for HttpRequestError {
@@ -24,100 +93,295 @@ impl fmt::Display for HttpRequestError {
 }
 
 /// HTTP method (GET, POST, ETC)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum HttpMethod {
     Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    /// Any other method, stored as received since methods are case-sensitive
+    Extension(String),
+}
+
+impl HttpMethod {
+    /// Returns the method as it appears in a request line.
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Connect => "CONNECT",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Trace => "TRACE",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Extension(method) => method,
+        }
+    }
+}
+
+impl fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 impl FromStr for HttpMethod {
     type Err = HttpRequestError;
 
     /// Creates an [`HttpMethod`] from a string containing the corresponding method.
-    /// Returns [`HttpMethod`] if string corresponds to an implemented method, else returns HttpRequestError.
+    /// Returns [`HttpMethod`] if string is a valid method token, else returns HttpRequestError.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "GET" => Ok(HttpMethod::Get),
-            _ => Err(HttpRequestError::new("Unknown http method")),
+            "HEAD" => Ok(HttpMethod::Head),
+            "POST" => Ok(HttpMethod::Post),
+            "PUT" => Ok(HttpMethod::Put),
+            "DELETE" => Ok(HttpMethod::Delete),
+            "CONNECT" => Ok(HttpMethod::Connect),
+            "OPTIONS" => Ok(HttpMethod::Options),
+            "TRACE" => Ok(HttpMethod::Trace),
+            "PATCH" => Ok(HttpMethod::Patch),
+            _ if is_token(s) => Ok(HttpMethod::Extension(String::from(s))),
+            _ => Err(HttpRequestError::new(
+                HttpRequestErrorKind::InvalidMethod,
+                "Invalid http method",
+            )),
         }
     }
 }
 
-/// HTTP protocol version
+/// HTTP protocol version. HTTP/0.9 requests, which have no version, are rejected with 505.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HttpVersion {
+    V10,
     V11,
 }
 
+impl HttpVersion {
+    /// Returns the version as it appears in request and status lines.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::V10 => "HTTP/1.0",
+            HttpVersion::V11 => "HTTP/1.1",
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 impl FromStr for HttpVersion {
     type Err = HttpRequestError;
 
     /// Creates an [`HttpVersion`] from a string containing the corresponding version.
-    /// Returns [`HttpVersion`] if string corresponds to an implemented method, else returns [`HttpRequestError`].
+    /// Unknown minor versions of HTTP/1 are handled as HTTP/1.1, the highest supported one.
+    /// Returns [`HttpVersion`] if string corresponds to a supported version, else returns [`HttpRequestError`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "HTTP/1.1" => Ok(HttpVersion::V11),
-            _ => Err(HttpRequestError::new("Unknown http version")),
+        match s.as_bytes() {
+            b"HTTP/1.0" => Ok(HttpVersion::V10),
+            [b'H', b'T', b'T', b'P', b'/', b'1', b'.', minor] if minor.is_ascii_digit() => {
+                Ok(HttpVersion::V11)
+            }
+            [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+                if major.is_ascii_digit() && minor.is_ascii_digit() =>
+            {
+                Err(HttpRequestError::new(
+                    HttpRequestErrorKind::UnsupportedVersion,
+                    "Unsupported http version",
+                ))
+            }
+            _ => Err(HttpRequestError::new(
+                HttpRequestErrorKind::InvalidVersion,
+                "Invalid http version",
+            )),
         }
     }
 }
 
 /// Stores HTTP request line information
+#[derive(Clone)]
 pub struct HttpRequestLine {
     pub method: HttpMethod,
+    /// Request target as received
     pub uri: String,
+    /// Request target split into its components
+    pub target: RequestTarget,
     pub version: HttpVersion,
 }
 
+impl HttpRequestLine {
+    /// Converts a request line borrowed from the input into an owned [`HttpRequestLine`].
+    fn from_raw(line: RequestLine) -> Result<HttpRequestLine, HttpRequestError> {
+        let method = HttpMethod::from_str(line.method)?;
+        let target = RequestTarget::parse(&method, line.target)?;
+        Ok(HttpRequestLine {
+            method,
+            uri: String::from(line.target),
+            target,
+            version: HttpVersion::from_str(line.version)?,
+        })
+    }
+}
+
 impl FromStr for HttpRequestLine {
     type Err = HttpRequestError;
 
-    /// Create an [`HttpRequestLine`] from a string containing the complete request line.
+    /// Create an [`HttpRequestLine`] from a string containing the complete request line, with or
+    /// without its line terminator.
     /// Returns [`HttpRequestLine`] if success, else return [`HttpRequestError`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let re = Regex::new(r#"([A-Z]*) (.*) (HTTP/[1-9.]*)"#)
-            .map_err(|_| HttpRequestError::new("Not able to parse input Http request"))?;
-
-        let caps = match re.captures(s) {
-            Some(caps) => caps,
-            None => {
-                return Err(HttpRequestError::new(
-                    "No captures found when parsing Http request",
-                ))
-            }
-        };
+        let line = s.strip_suffix('\n').unwrap_or(s);
+        let line = line.strip_suffix('\r').unwrap_or(line);
 
-        let method = match caps.get(1) {
-            Some(match_method) => HttpMethod::from_str(match_method.as_str())?,
-            None => {
-                return Err(HttpRequestError::new(
-                    "Http method not found in request line",
-                ))
-            }
-        };
+        HttpRequestLine::from_raw(parser::parse_request_line(line.as_bytes())?)
+    }
+}
 
-        let uri = match caps.get(2) {
-            Some(uri) => uri.as_str(),
-            None => return Err(HttpRequestError::new("Http uri not found in request line")),
-        };
+/// Parses the request line and the header fields of a request. Repeated headers are appended to
+/// the map.
+pub(crate) fn parse_head(head: &[u8]) -> Result<(HttpRequestLine, HeaderMap), HttpRequestError> {
+    let (line, fields) = parser::parse_head(head)?;
 
-        let version = match caps.get(3) {
-            Some(match_method) => HttpVersion::from_str(match_method.as_str())?,
-            None => {
-                return Err(HttpRequestError::new(
-                    "Http version not found in request line",
-                ))
-            }
-        };
-
-        Ok(HttpRequestLine {
-            method,
-            uri: String::from(uri),
-            version,
-        })
+    let mut headers = HeaderMap::with_capacity(fields.len());
+    for field in fields {
+        let (name, value) = field.to_header()?;
+        headers.append(name, value);
     }
+
+    Ok((HttpRequestLine::from_raw(line)?, headers))
 }
 
 /// Stores full HTTP request content
+#[derive(Clone)]
 pub struct HttpRequest {
     pub line: HttpRequestLine,
+    /// Header fields of the request. Names are case-insensitive and a name can hold several values.
+    pub headers: HeaderMap,
+    /// Body of the request, decoded from its transfer coding
+    pub body: Vec<u8>,
+    /// Trailer fields sent after a chunked body
+    pub trailers: HeaderMap,
+}
+
+impl HttpRequest {
+    /// Returns the percent-encoded path of the request target, without its query.
+    pub fn path(&self) -> &str {
+        self.line.target.path()
+    }
+
+    /// Returns the decoded parameters of the query of the request target.
+    pub fn query_params(&self) -> QueryParams {
+        self.line.target.query_params()
+    }
+
+    /// Parses the first value of a query parameter. Returns `None` if the parameter is missing.
+    /// # Example
+    ///
+    /// ```
+    /// use http_server::http::request::HttpRequest;
+    /// use std::str::FromStr;
+    ///
+    /// let request = HttpRequest::from_str("GET /list?page=2 HTTP/1.1\r\n\r\n").unwrap();
+    ///
+    /// assert_eq!(request.query_param::<u32>("page"), Some(Ok(2)));
+    /// ```
+    pub fn query_param<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>> {
+        self.query_params().get_as(name)
+    }
+
+    /// Returns the host targeted by the request. The authority of an absolute-form target takes
+    /// precedence over the `Host` header (RFC 9112 section 3.2.2).
+    pub fn host(&self) -> Option<&str> {
+        self.line
+            .target
+            .authority()
+            .or_else(|| self.headers.get(HOST)?.to_str().ok())
+    }
+
+    /// Returns true if the `Connection` header of the request contains the provided option.
+    /// Options are compared case-insensitively.
+    pub fn has_connection_option(&self, option: &str) -> bool {
+        self.headers
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case(option))
+    }
+
+    /// Returns true if the client asks for the connection to stay open after the response.
+    /// HTTP/1.1 connections are persistent unless the `close` option is sent, while HTTP/1.0
+    /// connections are only persistent if the `keep-alive` option is sent.
+    pub fn keep_alive(&self) -> bool {
+        match self.line.version {
+            HttpVersion::V10 => self.has_connection_option("keep-alive"),
+            HttpVersion::V11 => !self.has_connection_option("close"),
+        }
+    }
+
+    /// Parses the request at the start of `data`, rejecting bodies larger than `max_body_size`.
+    /// Returns the request and the number of bytes it uses, or `None` if `data` does not contain a
+    /// complete request yet. Returns [`HttpRequestError`] if the request is malformed.
+    pub fn parse(
+        data: &[u8],
+        max_body_size: usize,
+    ) -> Result<Option<(HttpRequest, usize)>, HttpRequestError> {
+        let head_end = match find_head_end(data) {
+            Some(head_end) => head_end,
+            None => return Ok(None),
+        };
+
+        let (line, headers) = parse_head(&data[..head_end])?;
+        let framing = BodyFraming::from_headers(&headers)?;
+
+        Ok(
+            decode_body(framing, &data[head_end..], max_body_size)?.map(|body| {
+                (
+                    HttpRequest {
+                        line,
+                        headers,
+                        body: body.data,
+                        trailers: body.trailers,
+                    },
+                    head_end + body.length,
+                )
+            }),
+        )
+    }
+
+    /// Creates an [`HttpRequest`] from bytes containing the complete HTTP request. If the empty line
+    /// ending the head is missing, all bytes are considered as part of the head.
+    /// Returns [`HttpRequest`] if success, else returns [`HttpRequestError`].
+    pub fn from_bytes(data: &[u8]) -> Result<HttpRequest, HttpRequestError> {
+        if find_head_end(data).is_none() {
+            let (line, headers) = parse_head(data)?;
+            return Ok(HttpRequest {
+                line,
+                headers,
+                body: Vec::new(),
+                trailers: HeaderMap::new(),
+            });
+        }
+
+        HttpRequest::parse(data, usize::MAX)?
+            .map(|(request, _)| request)
+            .ok_or_else(|| {
+                HttpRequestError::new(
+                    HttpRequestErrorKind::IncompleteBody,
+                    "Request body is shorter than announced",
+                )
+            })
+    }
 }
 
 impl FromStr for HttpRequest {
@@ -126,9 +390,7 @@ impl FromStr for HttpRequest {
     /// Creates an [`HttpRequest`] from a string containing the complete HTTP request.
     /// Returns [`HttpRequest`] if success, else returns [`HttpRequestError`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let line = HttpRequestLine::from_str(s)?;
-
-        Ok(HttpRequest { line })
+        HttpRequest::from_bytes(s.as_bytes())
     }
 }
 
@@ -145,13 +407,41 @@ mod tests {
         assert!(matches!(result, Ok(HttpMethod::Get)));
     }
 
+    #[test]
+    fn parse_all_standard_methods() {
+        let methods = [
+            ("HEAD", HttpMethod::Head),
+            ("POST", HttpMethod::Post),
+            ("PUT", HttpMethod::Put),
+            ("DELETE", HttpMethod::Delete),
+            ("CONNECT", HttpMethod::Connect),
+            ("OPTIONS", HttpMethod::Options),
+            ("TRACE", HttpMethod::Trace),
+            ("PATCH", HttpMethod::Patch),
+        ];
+
+        for (method, expected) in methods.iter() {
+            assert_eq!(&HttpMethod::from_str(method).expect(""), expected);
+            assert_eq!(expected.as_str(), *method);
+        }
+    }
+
     #[test]
     fn parse_unknown_method() {
         let method = "UNKNOWN";
 
         let result = HttpMethod::from_str(method);
 
-        assert!(matches!(result, Err(_)));
+        assert_eq!(
+            result.expect(""),
+            HttpMethod::Extension(String::from("UNKNOWN"))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_method() {
+        assert!(HttpMethod::from_str("").is_err());
+        assert!(HttpMethod::from_str("GE(T").is_err());
     }
 
     #[test]
@@ -164,6 +454,41 @@ mod tests {
     }
 
     #[test]
+    fn parse_10_version() {
+        let result = HttpVersion::from_str("HTTP/1.0");
+
+        assert!(matches!(result, Ok(HttpVersion::V10)));
+    }
+
+    #[test]
+    fn parse_higher_minor_version() {
+        let result = HttpVersion::from_str("HTTP/1.2");
+
+        assert!(matches!(result, Ok(HttpVersion::V11)));
+    }
+
+    #[test]
+    fn parse_unsupported_major_versions() {
+        for version in ["HTTP/0.9", "HTTP/2.0", "HTTP/3.0"].iter() {
+            let result = HttpVersion::from_str(version);
+
+            assert!(
+                matches!(result, Err(e) if e.status_code() == StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+            );
+        }
+    }
+
+    #[test]
+    fn reject_http_09_request() {
+        let result = HttpRequest::from_str("GET /index.html\r\n\r\n");
+
+        assert!(
+            matches!(result, Err(e) if e.status_code() == StatusCode::HTTP_VERSION_NOT_SUPPORTED)
+        );
+    }
+
+    #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn parse_unknown_version() {
         let version = "UNKNOWN";
 
@@ -184,8 +509,181 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn parse_wrong_request_line() {
         let request_line = "GET /index.html HTTP/.1 \r\n";
         assert!(matches!(HttpRequestLine::from_str(request_line), Err(_)));
     }
+
+    #[test]
+    fn parse_request_headers() {
+        let request =
+            "GET /index.html HTTP/1.1\r\nHost: localhost:5666\r\nAccept:text/html \r\n\r\n";
+
+        let result = HttpRequest::from_str(request).expect("");
+
+        assert_eq!(result.headers.len(), 2);
+        assert_eq!(result.headers["host"], "localhost:5666");
+        assert_eq!(result.headers["ACCEPT"], "text/html");
+    }
+
+    #[test]
+    fn parse_repeated_headers() {
+        let request = "GET / HTTP/1.1\r\nAccept: text/html\r\naccept: image/png\r\n\r\n";
+
+        let result = HttpRequest::from_str(request).expect("");
+
+        let values: Vec<_> = result.headers.get_all("Accept").iter().collect();
+        assert_eq!(values, vec!["text/html", "image/png"]);
+    }
+
+    #[test]
+    fn ignore_data_after_blank_line() {
+        let request = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\nNot-A-Header\0\0";
+
+        let result = HttpRequest::from_str(request).expect("");
+
+        assert_eq!(result.headers.len(), 1);
+    }
+
+    #[test]
+    fn parse_request_with_content_length_body() {
+        let request = b"POST /form HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+
+        let result = HttpRequest::from_bytes(request).expect("");
+
+        assert_eq!(result.body, b"hello".to_vec());
+    }
+
+    #[test]
+    fn parse_request_with_chunked_body() {
+        let request =
+            b"POST /form HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nX-Sum: 1\r\n\r\n";
+
+        let result = HttpRequest::from_bytes(request).expect("");
+
+        assert_eq!(result.body, b"hello".to_vec());
+        assert_eq!(result.trailers["x-sum"], "1");
+    }
+
+    #[test]
+    fn parse_request_with_incomplete_body() {
+        let request = b"POST /form HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello";
+
+        let result = HttpRequest::from_bytes(request);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::IncompleteBody));
+    }
+
+    #[test]
+    fn parse_incomplete_request() {
+        let request = b"POST /form HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello";
+
+        assert!(matches!(HttpRequest::parse(&request[..20], 1024), Ok(None)));
+        assert!(matches!(HttpRequest::parse(request, 1024), Ok(None)));
+    }
+
+    #[test]
+    fn parse_request_with_too_large_body() {
+        let request = b"POST /form HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello";
+
+        let result = HttpRequest::parse(request, 8);
+
+        assert!(matches!(result, Err(e) if e.status_code() == StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    #[test]
+    fn keep_alive_by_default() {
+        let request = HttpRequest::from_str("GET / HTTP/1.1\r\n\r\n").expect("");
+
+        assert!(request.keep_alive());
+    }
+
+    #[test]
+    fn close_by_default_for_10_version() {
+        let request = HttpRequest::from_str("GET / HTTP/1.0\r\n\r\n").expect("");
+
+        assert!(!request.keep_alive());
+    }
+
+    #[test]
+    fn keep_alive_requested_by_10_client() {
+        let request =
+            HttpRequest::from_str("GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").expect("");
+
+        assert!(request.keep_alive());
+    }
+
+    #[test]
+    fn close_requested_by_client() {
+        let request =
+            HttpRequest::from_str("GET / HTTP/1.1\r\nConnection: TE, Close\r\n\r\n").expect("");
+
+        assert!(!request.keep_alive());
+    }
+
+    #[test]
+    fn parse_header_without_colon() {
+        let request = "GET / HTTP/1.1\r\nHost localhost\r\n\r\n";
+
+        let result = HttpRequest::from_str(request);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::MalformedHeader));
+    }
+
+    #[test]
+    fn parse_header_with_whitespace_before_colon() {
+        let request = "GET / HTTP/1.1\r\nHost : localhost\r\n\r\n";
+
+        let result = HttpRequest::from_str(request);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidHeaderName));
+    }
+
+    #[test]
+    fn parse_folded_header() {
+        let request = "GET / HTTP/1.1\r\nX-Test: a\r\n b\r\n\r\n";
+
+        let result = HttpRequest::from_str(request);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::MalformedHeader));
+    }
+
+    #[test]
+    fn parse_header_with_invalid_value() {
+        let request = "GET / HTTP/1.1\r\nX-Test: a\x07b\r\n\r\n";
+
+        let result = HttpRequest::from_str(request);
+
+        assert!(matches!(result, Err(e) if e.kind() == HttpRequestErrorKind::InvalidHeaderValue));
+    }
+
+    #[test]
+    fn access_path_and_query_params() {
+        let request =
+            HttpRequest::from_str("GET /search%20page?q=rust+http&tag=a&tag=b HTTP/1.1\r\n\r\n")
+                .expect("");
+
+        assert_eq!(request.path(), "/search%20page");
+        assert_eq!(request.query_params().get("q"), Some("rust http"));
+        assert_eq!(
+            request.query_params().get_all("tag").collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert!(request.query_param::<u32>("q").expect("").is_err());
+    }
+
+    #[test]
+    fn absolute_form_authority_overrides_host() {
+        let request = HttpRequest::from_str(
+            "GET http://example.com/index.html HTTP/1.1\r\nHost: other.com\r\n\r\n",
+        )
+        .expect("");
+        let origin =
+            HttpRequest::from_str("GET /index.html HTTP/1.1\r\nHost: other.com\r\n\r\n").expect("");
+
+        assert_eq!(request.path(), "/index.html");
+        assert_eq!(request.host(), Some("example.com"));
+        assert_eq!(origin.host(), Some("other.com"));
+    }
 }
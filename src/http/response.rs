@@ -0,0 +1,599 @@
+use crate::http::request::HttpVersion;
+use http::header::{
+    HeaderMap, HeaderValue, IntoHeaderName, CONTENT_LENGTH, DATE, SERVER, TRANSFER_ENCODING,
+};
+use http::StatusCode;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+
+/// Default value of the `Server` header sent with every response
+pub const SERVER_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Default size of the buffer used to copy file and stream bodies
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Options controlling how the body of a response is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Size of the buffer used to copy file and stream bodies. It bounds the memory used by a
+    /// connection to send a body, whatever the size of the body.
+    pub buffer_size: usize,
+    /// Copies file bodies with `std::io::copy`, which lets the kernel transfer the data without
+    /// going through user space (`sendfile` or `copy_file_range` on Linux) when writing directly
+    /// to a socket or a file.
+    pub zero_copy: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            zero_copy: true,
+        }
+    }
+}
+
+/// Body of an HTTP response
+pub enum Body {
+    /// Body held in memory
+    Bytes(Vec<u8>),
+    /// `length` bytes read from the current position of a file
+    File { file: File, length: u64 },
+    /// Bytes produced by a reader. If `length` is `None`, the body lasts until the end of the
+    /// reader and is sent using the chunked transfer coding.
+    Stream {
+        reader: Box<dyn Read + Send>,
+        length: Option<u64>,
+    },
+}
+
+impl Body {
+    /// Returns the length of the body in bytes, or `None` if it is not known in advance.
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::File { length, .. } => Some(*length),
+            Body::Stream { length, .. } => *length,
+        }
+    }
+
+    /// Returns true if the body is known not to contain any byte.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Writes the whole body and returns the number of bytes written.
+    fn write_to<W: Write>(self, writer: &mut W, options: &WriteOptions) -> io::Result<u64> {
+        let (copied, length) = match self {
+            Body::Bytes(bytes) => return writer.write_all(&bytes).map(|_| bytes.len() as u64),
+            Body::File { file, length } if options.zero_copy => {
+                (io::copy(&mut file.take(length), writer)?, length)
+            }
+            Body::File { file, length } => (
+                copy_buffered(&mut file.take(length), writer, options.buffer_size)?,
+                length,
+            ),
+            Body::Stream {
+                reader,
+                length: Some(length),
+            } => (
+                copy_buffered(&mut reader.take(length), writer, options.buffer_size)?,
+                length,
+            ),
+            Body::Stream {
+                mut reader,
+                length: None,
+            } => return copy_buffered(&mut reader, writer, options.buffer_size),
+        };
+        check_length(copied, length)
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Body::File { length, .. } => write!(f, "File({} bytes)", length),
+            Body::Stream {
+                length: Some(length),
+                ..
+            } => write!(f, "Stream({} bytes)", length),
+            Body::Stream { length: None, .. } => write!(f, "Stream(unknown length)"),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Body::Bytes(bytes)
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Self {
+        Body::Bytes(text.as_bytes().to_vec())
+    }
+}
+
+/// Copies the reader until its end using a buffer of `buffer_size` bytes. Returns the number of
+/// bytes copied.
+fn copy_buffered<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    buffer_size: usize,
+) -> io::Result<u64> {
+    let mut buffer = vec![0; buffer_size.max(1)];
+    let mut copied = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(copied),
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+}
+
+/// Checks that a body with an announced length was copied completely. Fails if the body ended
+/// early, since the announced Content-Length could not be respected anymore.
+fn check_length(copied: u64, length: u64) -> io::Result<u64> {
+    if copied < length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Body ended before its announced length",
+        ));
+    }
+    Ok(copied)
+}
+
+/// Writer encoding everything written into it with the chunked transfer coding
+struct ChunkedWriter<'a, W: Write> {
+    writer: &'a mut W,
+}
+
+impl<'a, W: Write> ChunkedWriter<'a, W> {
+    /// Writes the last chunk, which ends the body.
+    fn finish(self) -> io::Result<()> {
+        self.writer.write_all(b"0\r\n\r\n")
+    }
+}
+
+impl<'a, W: Write> Write for ChunkedWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // An empty chunk would be read as the end of the body
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.writer, "{:X}\r\n", buf.len())?;
+        self.writer.write_all(buf)?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes header names with the usual capitalization, e.g. `Content-Length`.
+fn write_header_name(head: &mut Vec<u8>, name: &str) {
+    let mut capitalize = true;
+    for byte in name.bytes() {
+        head.push(if capitalize {
+            byte.to_ascii_uppercase()
+        } else {
+            byte
+        });
+        capitalize = byte == b'-';
+    }
+}
+
+/// HTTP response made of a status, header fields and a body
+#[derive(Debug)]
+pub struct HttpResponse {
+    version: HttpVersion,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Body,
+    send_body: bool,
+}
+
+impl HttpResponse {
+    /// Creates a new [`HttpResponse`] with the provided status and an empty body.
+    pub fn new(status: StatusCode) -> HttpResponse {
+        HttpResponse::builder().status(status).build()
+    }
+
+    /// Returns a new [`HttpResponseBuilder`], which creates a `200 OK` response by default.
+    /// # Example
+    ///
+    /// ```
+    /// use http::header::{HeaderValue, CONTENT_TYPE};
+    /// use http::StatusCode;
+    /// use http_server::http::response::HttpResponse;
+    ///
+    /// let response = HttpResponse::builder()
+    ///     .status(StatusCode::NOT_FOUND)
+    ///     .header(CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+    ///     .body("404 - Page not found")
+    ///     .build();
+    /// ```
+    pub fn builder() -> HttpResponseBuilder {
+        HttpResponseBuilder::new()
+    }
+
+    /// Returns the version used in the status line.
+    pub fn version(&self) -> HttpVersion {
+        self.version
+    }
+
+    /// Returns the version used in the status line for modification. Connections set it to the
+    /// version of the request, which decides how the body can be delimited.
+    pub fn version_mut(&mut self) -> &mut HttpVersion {
+        &mut self.version
+    }
+
+    /// Returns the status of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the header fields of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the header fields of the response for modification.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    /// Keeps the headers describing the body, including its Content-Length, but does not send
+    /// the body itself. Used to answer HEAD requests.
+    pub fn omit_body(mut self) -> HttpResponse {
+        self.send_body = false;
+        self
+    }
+
+    /// Returns true if the client can find the end of the response without the connection being
+    /// closed. HTTP/1.0 clients do not support chunked bodies, so a body of unknown length can
+    /// only be delimited by closing the connection.
+    pub fn is_delimited(&self) -> bool {
+        self.version == HttpVersion::V11
+            || self.body.len().is_some()
+            || !self.send_body
+            || !self.status_allows_body()
+    }
+
+    /// Returns true if the body is sent using the chunked transfer coding.
+    fn is_chunked(&self) -> bool {
+        self.version == HttpVersion::V11 && self.body.len().is_none() && self.status_allows_body()
+    }
+
+    /// Returns true if a body is allowed with the status of the response.
+    fn status_allows_body(&self) -> bool {
+        !(self.status.is_informational()
+            || self.status == StatusCode::NO_CONTENT
+            || self.status == StatusCode::NOT_MODIFIED)
+    }
+
+    /// Serializes the status line and the header fields. `Date` and `Server` headers are always
+    /// added, as well as `Content-Length`, or `Transfer-Encoding` when the length is unknown.
+    fn serialize_head(&mut self) -> Vec<u8> {
+        self.headers.remove(CONTENT_LENGTH);
+        self.headers.remove(TRANSFER_ENCODING);
+        if self.is_chunked() {
+            self.headers
+                .insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        } else if let (true, Some(length)) = (self.status_allows_body(), self.body.len()) {
+            self.headers
+                .insert(CONTENT_LENGTH, HeaderValue::from(length));
+        }
+        if !self.headers.contains_key(DATE) {
+            let date = httpdate::fmt_http_date(SystemTime::now());
+            if let Ok(date) = HeaderValue::from_str(&date) {
+                self.headers.insert(DATE, date);
+            }
+        }
+        if !self.headers.contains_key(SERVER) {
+            self.headers
+                .insert(SERVER, HeaderValue::from_static(SERVER_NAME));
+        }
+
+        let mut head = format!(
+            "{} {} {}\r\n",
+            self.version,
+            self.status.as_str(),
+            self.status.canonical_reason().unwrap_or("")
+        )
+        .into_bytes();
+        for (name, value) in self.headers.iter() {
+            write_header_name(&mut head, name.as_str());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        head
+    }
+
+    /// Writes the complete response and returns the number of body bytes written.
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<u64> {
+        self.write_to_with(writer, &WriteOptions::default())
+    }
+
+    /// Writes the complete response using the provided options and returns the number of body
+    /// bytes written.
+    pub fn write_to_with<W: Write>(
+        mut self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> io::Result<u64> {
+        let head = self.serialize_head();
+        writer.write_all(&head)?;
+
+        let written = if !self.send_body || !self.status_allows_body() {
+            0
+        } else if self.is_chunked() {
+            let mut chunked = ChunkedWriter { writer };
+            let written = self.body.write_to(&mut chunked, options)?;
+            chunked.finish()?;
+            written
+        } else {
+            self.body.write_to(writer, options)?
+        };
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Serializes the complete response into a vector of bytes.
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Builder of [`HttpResponse`]
+#[derive(Debug)]
+pub struct HttpResponseBuilder {
+    response: HttpResponse,
+}
+
+impl HttpResponseBuilder {
+    /// Creates a new [`HttpResponseBuilder`] for a `200 OK` HTTP/1.1 response with an empty body.
+    pub fn new() -> HttpResponseBuilder {
+        HttpResponseBuilder {
+            response: HttpResponse {
+                version: HttpVersion::V11,
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: Body::Bytes(Vec::new()),
+                send_body: true,
+            },
+        }
+    }
+
+    /// Sets the version used in the status line.
+    pub fn version(mut self, version: HttpVersion) -> HttpResponseBuilder {
+        self.response.version = version;
+        self
+    }
+
+    /// Sets the status of the response.
+    pub fn status(mut self, status: StatusCode) -> HttpResponseBuilder {
+        self.response.status = status;
+        self
+    }
+
+    /// Adds a header field. Values are appended if the header is already present.
+    pub fn header<K: IntoHeaderName>(mut self, name: K, value: HeaderValue) -> HttpResponseBuilder {
+        self.response.headers.append(name, value);
+        self
+    }
+
+    /// Sets the body of the response.
+    pub fn body<B: Into<Body>>(mut self, body: B) -> HttpResponseBuilder {
+        self.response.body = body.into();
+        self
+    }
+
+    /// Returns the built [`HttpResponse`].
+    pub fn build(self) -> HttpResponse {
+        self.response
+    }
+}
+
+impl Default for HttpResponseBuilder {
+    fn default() -> Self {
+        HttpResponseBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::CONTENT_TYPE;
+
+    fn serialize(response: HttpResponse) -> String {
+        String::from_utf8(response.into_bytes().expect("")).expect("")
+    }
+
+    #[test]
+    fn serialize_response_with_body() {
+        let response = HttpResponse::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(CONTENT_TYPE, HeaderValue::from_static("text/plain"))
+            .body("Not found")
+            .build();
+
+        let result = serialize(response);
+
+        assert!(result.starts_with("HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n"));
+        assert!(result.contains("\r\nContent-Length: 9\r\n"));
+        assert!(result.contains("\r\nDate: "));
+        assert!(result.contains(&format!("\r\nServer: {}\r\n", SERVER_NAME)));
+        assert!(result.ends_with("\r\n\r\nNot found"));
+    }
+
+    #[test]
+    fn content_length_overrides_user_value() {
+        let response = HttpResponse::builder()
+            .header(CONTENT_LENGTH, HeaderValue::from_static("42"))
+            .body("hello")
+            .build();
+
+        let result = serialize(response);
+
+        assert!(result.contains("\r\nContent-Length: 5\r\n"));
+        assert!(!result.contains("Content-Length: 42"));
+    }
+
+    #[test]
+    fn serialize_response_without_body() {
+        let response = HttpResponse::builder().body("hello").build().omit_body();
+
+        let result = serialize(response);
+
+        assert!(result.contains("\r\nContent-Length: 5\r\n"));
+        assert!(result.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn no_content_length_for_not_modified() {
+        let response = HttpResponse::new(StatusCode::NOT_MODIFIED);
+
+        let result = serialize(response);
+
+        assert!(!result.contains("Content-Length"));
+    }
+
+    #[test]
+    fn serialize_stream_body() {
+        let response = HttpResponse::builder()
+            .version(HttpVersion::V10)
+            .body(Body::Stream {
+                reader: Box::new(io::Cursor::new(b"hello world".to_vec())),
+                length: Some(5),
+            })
+            .build();
+
+        let result = serialize(response);
+
+        assert!(result.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(result.ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn fail_on_truncated_stream_body() {
+        let response = HttpResponse::builder()
+            .body(Body::Stream {
+                reader: Box::new(io::Cursor::new(b"hello".to_vec())),
+                length: Some(10),
+            })
+            .build();
+
+        assert!(response.into_bytes().is_err());
+    }
+
+    #[test]
+    fn serialize_stream_of_unknown_length_as_chunked() {
+        let response = HttpResponse::builder()
+            .body(Body::Stream {
+                reader: Box::new(io::Cursor::new(b"hello world".to_vec())),
+                length: None,
+            })
+            .build();
+
+        assert!(response.is_delimited());
+
+        let result = serialize(response);
+
+        assert!(result.contains("\r\nTransfer-Encoding: chunked\r\n"));
+        assert!(!result.contains("Content-Length"));
+        assert!(result.ends_with("\r\n\r\nB\r\nhello world\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn serialize_stream_of_unknown_length_for_10_client() {
+        let response = HttpResponse::builder()
+            .version(HttpVersion::V10)
+            .body(Body::Stream {
+                reader: Box::new(io::Cursor::new(b"hello world".to_vec())),
+                length: None,
+            })
+            .build();
+
+        assert!(!response.is_delimited());
+
+        let result = serialize(response);
+
+        assert!(!result.contains("Transfer-Encoding"));
+        assert!(!result.contains("Content-Length"));
+        assert!(result.ends_with("\r\n\r\nhello world"));
+    }
+
+    #[test]
+    fn date_uses_imf_fixdate_format() {
+        let mut response = HttpResponse::new(StatusCode::OK);
+        response.serialize_head();
+
+        let date = response.headers()[DATE].to_str().expect("");
+
+        assert!(httpdate::parse_http_date(date).is_ok());
+        assert!(date.ends_with(" GMT"));
+    }
+
+    /// Writer recording the size of the largest write
+    struct RecordingWriter {
+        bytes: Vec<u8>,
+        largest_write: usize,
+    }
+
+    impl Write for RecordingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.largest_write = self.largest_write.max(buf.len());
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_file_body_in_chunks() {
+        let file = File::open("example/hello.html").expect("");
+        let length = file.metadata().expect("").len();
+        let response = HttpResponse::builder()
+            .body(Body::File { file, length })
+            .build();
+        let mut writer = RecordingWriter {
+            bytes: Vec::new(),
+            largest_write: 0,
+        };
+        let options = WriteOptions {
+            buffer_size: 16,
+            zero_copy: false,
+        };
+
+        let written = response.write_to_with(&mut writer, &options).expect("");
+
+        assert_eq!(written, length);
+        assert!(writer.bytes.ends_with(b"</html>"));
+        // Only the head is written at once
+        let head_length = writer.bytes.len() - length as usize;
+        assert_eq!(writer.largest_write, head_length.max(16));
+    }
+}
@@ -0,0 +1,621 @@
+use crate::http::content::build_content_type;
+use crate::http::request::{HttpMethod, HttpRequest};
+use crate::http::response::HttpResponse;
+use crate::http::server::{Handler, ServerError};
+use crate::http::uri::{percent_decode, TargetForm};
+use http::header::{HeaderValue, ALLOW, CONTENT_TYPE};
+use http::StatusCode;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Handler of the requests matching a route. It is implemented by closures taking the request
+/// and the parameters extracted from its path.
+pub trait RouteHandler: Send + Sync {
+    /// Returns the response to a request matching the route.
+    fn handle(&self, request: &HttpRequest, params: &Params) -> Result<HttpResponse, ServerError>;
+}
+
+impl<F> RouteHandler for F
+where
+    F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync,
+{
+    fn handle(&self, request: &HttpRequest, params: &Params) -> Result<HttpResponse, ServerError> {
+        self(request, params)
+    }
+}
+
+/// Error returned when a path pattern is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    msg: String,
+}
+
+impl PatternError {
+    fn new(msg: &str) -> PatternError {
+        PatternError {
+            msg: String::from(msg),
+        }
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+/// Segment of a path pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Segment which must be equal to the segment of the path
+    Static(String),
+    /// `:name`, matching any non-empty segment
+    Param(String),
+    /// `*name`, matching the rest of the path, possibly empty
+    Wildcard(String),
+}
+
+/// Pattern matched against the path of requests, e.g. `/users/:id` or `/static/*path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern {
+    pattern: String,
+    segments: Vec<Segment>,
+}
+
+impl PathPattern {
+    /// Parses a pattern. Patterns start with `/`, parameters are segments starting with `:` and
+    /// a wildcard, starting with `*`, can only be the last segment.
+    pub fn parse(pattern: &str) -> Result<PathPattern, PatternError> {
+        let rest = pattern
+            .strip_prefix('/')
+            .ok_or_else(|| PatternError::new("Pattern must start with '/'"))?;
+        let parts: Vec<&str> = rest.split('/').collect();
+        let mut segments = Vec::with_capacity(parts.len());
+
+        for (index, part) in parts.iter().enumerate() {
+            let segment = if let Some(name) = part.strip_prefix(':') {
+                Segment::Param(String::from(name))
+            } else if let Some(name) = part.strip_prefix('*') {
+                if index + 1 != parts.len() {
+                    return Err(PatternError::new("Wildcard must be the last segment"));
+                }
+                Segment::Wildcard(String::from(name))
+            } else {
+                Segment::Static(String::from(*part))
+            };
+            if let Segment::Param(name) | Segment::Wildcard(name) = &segment {
+                if name.is_empty() {
+                    return Err(PatternError::new("Parameter has no name"));
+                }
+                let duplicate = segments.iter().any(|segment| {
+                    matches!(segment, Segment::Param(other) | Segment::Wildcard(other) if other == name)
+                });
+                if duplicate {
+                    return Err(PatternError::new("Parameter name is used twice"));
+                }
+            }
+            segments.push(segment);
+        }
+
+        Ok(PathPattern {
+            pattern: String::from(pattern),
+            segments,
+        })
+    }
+
+    /// Returns the pattern as it was written.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Matches a percent-encoded path against the pattern. Returns the parameters extracted from
+    /// the path, or `None` if the path does not match.
+    pub fn matches(&self, path: &str) -> Option<Params> {
+        let parts: Vec<&str> = path.strip_prefix('/')?.split('/').collect();
+        let mut params = Params::default();
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            if let Segment::Wildcard(name) = segment {
+                // Mounted handlers, like static files, answer invalid paths themselves
+                let raw = parts[index.min(parts.len())..].join("/");
+                let value = decode(&raw).unwrap_or_else(|| raw.clone());
+                params.push(name, value, raw);
+                return Some(params);
+            }
+            let part = parts.get(index)?;
+            match segment {
+                Segment::Static(expected) => {
+                    if decode(part)? != *expected {
+                        return None;
+                    }
+                }
+                Segment::Param(name) if !part.is_empty() => {
+                    params.push(name, decode(part)?, String::from(*part))
+                }
+                _ => return None,
+            }
+        }
+        (parts.len() == self.segments.len()).then_some(params)
+    }
+
+    /// Returns a key ordering patterns from the least to the most specific: static segments are
+    /// more specific than parameters, which are more specific than wildcards.
+    fn specificity(&self) -> Vec<u8> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Static(_) => 2,
+                Segment::Param(_) => 1,
+                Segment::Wildcard(_) => 0,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// Decodes a segment of a path. Returns `None` if it is not valid once decoded.
+fn decode(segment: &str) -> Option<String> {
+    percent_decode(segment).and_then(|decoded| String::from_utf8(decoded).ok())
+}
+
+/// Parameter extracted from the path of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Param {
+    name: String,
+    value: String,
+    raw: String,
+}
+
+/// Parameters extracted from the path of a request by the pattern of a route
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    params: Vec<Param>,
+}
+
+impl Params {
+    fn push(&mut self, name: &str, value: String, raw: String) {
+        self.params.push(Param {
+            name: String::from(name),
+            value,
+            raw,
+        });
+    }
+
+    /// Returns the decoded value of a parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|param| param.name == name)
+            .map(|param| param.value.as_str())
+    }
+
+    /// Returns the value of a parameter as it appears in the path, percent-encoded.
+    pub fn get_raw(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|param| param.name == name)
+            .map(|param| param.raw.as_str())
+    }
+
+    /// Parses the value of a parameter. Returns `None` if the parameter is missing.
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>> {
+        self.get(name).map(str::parse)
+    }
+
+    /// Returns all the decoded parameters, in the order of the pattern.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|param| (param.name.as_str(), param.value.as_str()))
+    }
+
+    /// Returns the number of parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns true if the pattern did not contain any parameter.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// Route registered on a [`Router`]
+#[derive(Clone)]
+struct Route {
+    method: HttpMethod,
+    pattern: PathPattern,
+    handler: Arc<dyn RouteHandler>,
+}
+
+/// Dispatches requests to the handler registered for their method and path.
+///
+/// When no route matches the path, the router answers `404 Not Found`. When routes match the
+/// path but not the method, it answers `405 Method Not Allowed` listing the methods of these
+/// routes. HEAD requests are handled by GET routes and OPTIONS requests are answered
+/// automatically. The most specific pattern wins when several routes match a request.
+/// # Example
+///
+/// ```
+/// use http::StatusCode;
+/// use http_server::http::response::HttpResponse;
+/// use http_server::http::router::Router;
+///
+/// let router = Router::new()
+///     .get("/users/:id", |_request, params| {
+///         let id = params.get("id").unwrap_or("");
+///         Ok(HttpResponse::builder().body(format!("User {}", id).as_str()).build())
+///     })
+///     .delete("/users/:id", |_request, _params| {
+///         Ok(HttpResponse::new(StatusCode::NO_CONTENT))
+///     });
+/// ```
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.routes
+                    .iter()
+                    .map(|route| format!("{} {}", route.method, route.pattern)),
+            )
+            .finish()
+    }
+}
+
+impl Router {
+    /// Creates a new [`Router`] without any route.
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Registers a handler for the requests with this method and a path matching the pattern.
+    /// # Panics
+    /// Panics if the pattern is invalid, see [`PathPattern::parse`].
+    pub fn route_handler<H: RouteHandler + 'static>(
+        mut self,
+        method: HttpMethod,
+        pattern: &str,
+        handler: H,
+    ) -> Router {
+        let pattern = match PathPattern::parse(pattern) {
+            Ok(pattern) => pattern,
+            Err(error) => panic!("Invalid route pattern {:?}: {}", pattern, error),
+        };
+        self.routes.push(Route {
+            method,
+            pattern,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    /// Registers a closure handling the requests with this method and a path matching the
+    /// pattern.
+    /// # Panics
+    /// Panics if the pattern is invalid, see [`PathPattern::parse`].
+    pub fn route<F>(self, method: HttpMethod, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.route_handler(method, pattern, handler)
+    }
+
+    /// Registers a closure handling GET, and HEAD, requests.
+    pub fn get<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.route(HttpMethod::Get, pattern, handler)
+    }
+
+    /// Registers a closure handling POST requests.
+    pub fn post<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.route(HttpMethod::Post, pattern, handler)
+    }
+
+    /// Registers a closure handling PUT requests.
+    pub fn put<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.route(HttpMethod::Put, pattern, handler)
+    }
+
+    /// Registers a closure handling DELETE requests.
+    pub fn delete<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.route(HttpMethod::Delete, pattern, handler)
+    }
+
+    /// Registers a closure handling PATCH requests.
+    pub fn patch<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.route(HttpMethod::Patch, pattern, handler)
+    }
+
+    /// Registers a handler for the GET requests of all the paths below `prefix`. The rest of the
+    /// path is given to the handler as the `path` parameter, e.g. `css/style.css` for
+    /// `/static/css/style.css` when mounted on `/static`. Used to serve files with
+    /// [`StaticFiles`](crate::http::static_files::StaticFiles).
+    pub fn mount<H: RouteHandler + 'static>(self, prefix: &str, handler: H) -> Router {
+        let pattern = format!("{}/*path", prefix.trim_end_matches('/'));
+        self.route_handler(HttpMethod::Get, &pattern, handler)
+    }
+
+    /// Returns the route with the most specific pattern among the matched routes for this
+    /// method. The first registered route wins between patterns as specific.
+    fn best_match<'a>(
+        matched: &'a [(&'a Route, Params)],
+        method: &HttpMethod,
+    ) -> Option<(&'a Route, &'a Params)> {
+        matched
+            .iter()
+            .rev()
+            .filter(|(route, _)| route.method == *method)
+            .max_by_key(|(route, _)| route.pattern.specificity())
+            .map(|(route, params)| (*route, params))
+    }
+
+    /// Returns the methods allowed by a list of routes, including HEAD for GET routes and
+    /// OPTIONS.
+    fn allowed_methods(routes: Vec<&Route>) -> String {
+        let mut methods: Vec<&HttpMethod> = Vec::new();
+        for route in routes {
+            if !methods.contains(&&route.method) {
+                methods.push(&route.method);
+            }
+        }
+        if methods.contains(&&HttpMethod::Get) && !methods.contains(&&HttpMethod::Head) {
+            let position = methods
+                .iter()
+                .position(|method| **method == HttpMethod::Get)
+                .map_or(0, |position| position + 1);
+            methods.insert(position, &HttpMethod::Head);
+        }
+        if !methods.contains(&&HttpMethod::Options) {
+            methods.push(&HttpMethod::Options);
+        }
+        methods
+            .iter()
+            .map(|method| method.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Generate a response listing the methods of the routes, answering an OPTIONS request or
+    /// a request with a method which is not allowed
+    fn build_allow_response(status: StatusCode, routes: Vec<&Route>) -> HttpResponse {
+        let response = HttpResponse::builder().status(status);
+        match HeaderValue::from_str(&Self::allowed_methods(routes)) {
+            Ok(allow) => response.header(ALLOW, allow),
+            Err(_) => response,
+        }
+        .build()
+    }
+
+    /// Generate the response sent when no route matches the path of a request
+    fn build_not_found_response() -> HttpResponse {
+        HttpResponse::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(CONTENT_TYPE, build_content_type(&mime::TEXT_PLAIN))
+            .body("404 - Page not found")
+            .build()
+    }
+}
+
+impl Handler for Router {
+    /// Dispatches a request to the handler of the matching route.
+    fn handle(&self, request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        let method = &request.line.method;
+
+        if matches!(method, HttpMethod::Extension(_))
+            && !self.routes.iter().any(|route| route.method == *method)
+        {
+            return Ok(HttpResponse::builder()
+                .status(StatusCode::NOT_IMPLEMENTED)
+                .build());
+        }
+        // OPTIONS * asks for the capabilities of the whole server
+        if request.line.target.form() == TargetForm::Asterisk {
+            return Ok(Self::build_allow_response(
+                StatusCode::OK,
+                self.routes.iter().collect(),
+            ));
+        }
+
+        let matched: Vec<(&Route, Params)> = self
+            .routes
+            .iter()
+            .filter_map(|route| Some((route, route.pattern.matches(request.path())?)))
+            .collect();
+        if matched.is_empty() {
+            return Ok(Self::build_not_found_response());
+        }
+
+        if let Some((route, params)) = Self::best_match(&matched, method) {
+            return route.handler.handle(request, params);
+        }
+        match method {
+            HttpMethod::Head => {
+                if let Some((route, params)) = Self::best_match(&matched, &HttpMethod::Get) {
+                    return Ok(route.handler.handle(request, params)?.omit_body());
+                }
+            }
+            HttpMethod::Options => {
+                let routes = matched.iter().map(|(route, _)| *route).collect();
+                return Ok(Self::build_allow_response(StatusCode::OK, routes));
+            }
+            _ => (),
+        }
+
+        let routes = matched.iter().map(|(route, _)| *route).collect();
+        Ok(Self::build_allow_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            routes,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::files::DocumentRoot;
+    use crate::http::server::ServerConfig;
+    use crate::http::static_files::StaticFiles;
+
+    fn respond(request: &str, router: &Router) -> String {
+        let request = HttpRequest::from_str(request).unwrap();
+        let response = router.handle(&request).unwrap();
+        String::from_utf8_lossy(&response.into_bytes().unwrap()).into_owned()
+    }
+
+    fn echo(
+        name: &'static str,
+    ) -> impl Fn(&HttpRequest, &Params) -> Result<HttpResponse, ServerError> {
+        move |_, params| {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            Ok(HttpResponse::builder()
+                .body(format!("{} {}", name, params.join("&")).as_str())
+                .build())
+        }
+    }
+
+    fn files_router() -> Router {
+        Router::new().mount(
+            "/",
+            StaticFiles::new(ServerConfig {
+                root: DocumentRoot::new("example"),
+                ..ServerConfig::default()
+            }),
+        )
+    }
+
+    #[test]
+    fn match_path_patterns() {
+        let pattern = PathPattern::parse("/users/:id/posts/:post").expect("");
+        let wildcard = PathPattern::parse("/static/*path").expect("");
+
+        let params = pattern.matches("/users/J%C3%B6rg/posts/12").expect("");
+        assert_eq!(params.get("id"), Some("Jörg"));
+        assert_eq!(params.get_raw("id"), Some("J%C3%B6rg"));
+        assert_eq!(params.get_as::<u32>("post"), Some(Ok(12)));
+        assert_eq!(pattern.matches("/users//posts/12"), None);
+        assert_eq!(pattern.matches("/users/1/posts"), None);
+        assert_eq!(pattern.matches("/users/1/posts/2/3"), None);
+
+        assert_eq!(
+            wildcard
+                .matches("/static/css/a%20b.css")
+                .expect("")
+                .get("path"),
+            Some("css/a b.css")
+        );
+        assert_eq!(wildcard.matches("/static").expect("").get("path"), Some(""));
+        assert_eq!(wildcard.matches("/staticfiles/a"), None);
+    }
+
+    #[test]
+    fn reject_invalid_patterns() {
+        assert!(PathPattern::parse("users").is_err());
+        assert!(PathPattern::parse("/*path/more").is_err());
+        assert!(PathPattern::parse("/users/:").is_err());
+        assert!(PathPattern::parse("/:id/:id").is_err());
+    }
+
+    #[test]
+    fn dispatch_to_most_specific_route() {
+        let router = Router::new()
+            .get("/*path", echo("wildcard"))
+            .get("/users/:id", echo("user"))
+            .get("/users/new", echo("new"))
+            .post("/users", echo("create"));
+
+        assert!(respond("GET /users/new HTTP/1.1\r\n\r\n", &router).ends_with("\r\n\r\nnew "));
+        assert!(respond("GET /users/42 HTTP/1.1\r\n\r\n", &router).ends_with("\r\n\r\nuser id=42"));
+        assert!(respond("GET /users/42/x HTTP/1.1\r\n\r\n", &router)
+            .ends_with("\r\n\r\nwildcard path=users/42/x"));
+        assert!(respond("POST /users HTTP/1.1\r\n\r\n", &router).ends_with("\r\n\r\ncreate "));
+    }
+
+    #[test]
+    fn answer_not_found_and_method_not_allowed() {
+        let router = Router::new()
+            .get("/users/:id", echo("user"))
+            .delete("/users/:id", echo("delete"));
+
+        let not_found = respond("GET /posts/1 HTTP/1.1\r\n\r\n", &router);
+        let not_allowed = respond("PUT /users/1 HTTP/1.1\r\n\r\n", &router);
+        let options = respond("OPTIONS /users/1 HTTP/1.1\r\n\r\n", &router);
+        let head = respond("HEAD /users/1 HTTP/1.1\r\n\r\n", &router);
+
+        assert!(not_found.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(not_allowed.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(not_allowed.contains("Allow: GET, HEAD, DELETE, OPTIONS\r\n"));
+        assert!(options.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(options.contains("Allow: GET, HEAD, DELETE, OPTIONS\r\n"));
+        assert!(head.contains("Content-Length: 9\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn serve_mounted_static_files() {
+        let router = files_router().get("/api/:name", echo("api"));
+        let mounted = Router::new().mount(
+            "/static/",
+            StaticFiles::new(ServerConfig {
+                root: DocumentRoot::new("example"),
+                ..ServerConfig::default()
+            }),
+        );
+
+        assert!(respond("GET /hello.html HTTP/1.1\r\n\r\n", &router)
+            .contains("Content-Length: 159\r\n"));
+        assert!(respond("GET /api/x HTTP/1.1\r\n\r\n", &router).ends_with("\r\n\r\napi name=x"));
+        assert!(respond("GET /static/hello.html HTTP/1.1\r\n\r\n", &mounted)
+            .contains("Content-Length: 159\r\n"));
+        assert!(respond("GET /hello.html HTTP/1.1\r\n\r\n", &mounted).starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn options_request_lists_allowed_methods() {
+        let response = respond("OPTIONS * HTTP/1.1\r\n\r\n", &files_router());
+
+        assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+    }
+
+    #[test]
+    fn unsupported_method_is_not_allowed() {
+        let response = respond("DELETE /hello.html HTTP/1.1\r\n\r\n", &files_router());
+
+        assert!(response.starts_with("HTTP/1.1 405"));
+        assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+    }
+
+    #[test]
+    fn unknown_method_is_not_implemented() {
+        let response = respond("PURGE /hello.html HTTP/1.1\r\n\r\n", &files_router());
+
+        assert!(response.starts_with("HTTP/1.1 501"));
+    }
+}
@@ -1,8 +1,15 @@
-use crate::http::content::{build_content_type, find_mimetype, load_content_from_uri, Message};
-use crate::http::request::{HttpMethod, HttpRequest};
-use http::StatusCode;
+use crate::connection::shutdown::ShutdownHandle;
+use crate::http::compression::CompressionConfig;
+use crate::http::files::DocumentRoot;
+use crate::http::middleware::{Middleware, Next, Pipeline};
+use crate::http::mime_types::MimeTypes;
+use crate::http::request::{HttpRequest, HttpRequestError};
+use crate::http::response::HttpResponse;
+use crate::http::router::Router;
+use crate::http::static_files::StaticFiles;
 use std::fmt;
-use std::str::FromStr;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Error returned when using server methods
 #[derive(Debug, Clone)]
@@ -12,7 +19,7 @@ pub struct ServerError {
 
 impl ServerError {
     /// Creates a new [`ServerError`]. An error message should be provided when building the error.
-    pub(crate) fn new(msg: &str) -> ServerError {
+    pub fn new(msg: &str) -> ServerError {
         ServerError {
             msg: String::from(msg),
         }
@@ -25,16 +32,93 @@ impl fmt::Display for ServerError {
     }
 }
 
+/// Generate the response sent back when a request could not be parsed
+pub(crate) fn build_error_response(error: &HttpRequestError) -> HttpResponse {
+    HttpResponse::new(error.status_code())
+}
+
+/// Application logic turning a request into the response sent back.
+///
+/// It is implemented by closures, by [`Router`] and by [`StaticFiles`]. Handlers are shared
+/// between the threads serving connections, so state they carry should be wrapped in an [`Arc`]
+/// and use interior mutability when it changes.
+/// # Example
+///
+/// ```
+/// use http_server::http::request::HttpRequest;
+/// use http_server::http::response::HttpResponse;
+/// use http_server::http::server::{Handler, ServerError};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// struct Counter {
+///     hits: Arc<AtomicUsize>,
+/// }
+///
+/// impl Handler for Counter {
+///     fn handle(&self, _request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+///         let hits = self.hits.fetch_add(1, Ordering::Relaxed) + 1;
+///         Ok(HttpResponse::builder().body(format!("Hit {}", hits).as_str()).build())
+///     }
+/// }
+/// ```
+pub trait Handler: Send + Sync {
+    /// Returns the response to a request. If failure occurs when handling the request, should
+    /// return ServerError, and the connection is closed without response.
+    fn handle(&self, request: &HttpRequest) -> Result<HttpResponse, ServerError>;
+}
+
+impl<F> Handler for F
+where
+    F: Fn(&HttpRequest) -> Result<HttpResponse, ServerError> + Send + Sync,
+{
+    fn handle(&self, request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        self(request)
+    }
+}
+
 /// Trait for an HTTP connection used by the server to handle request
 pub trait Connection {
-    /// Starts to loop over the input connection and handle incoming data with provided callback.
-    /// # Arguments
-    /// `callback` accepts a vector of bytes and returns a vector of bytes containing the HTTP
-    ///     response. If failure occurs when handling request, should return ServerError.
-    fn listen<T: 'static + Copy + Fn(&[u8]) -> Result<Vec<u8>, ServerError> + Send + Sync>(
-        &self,
-        callback: T,
-    );
+    /// Starts to loop over the input connection and handle incoming requests with provided
+    /// handler. The handler is shared by all the requests. Returns once shutdown is requested
+    /// through the handle and the requests being served are finished.
+    fn listen<H: Handler + ?Sized + 'static>(&self, handler: Arc<H>, shutdown: ShutdownHandle);
+}
+
+/// Configuration of the files served by a [`Server`], see [`StaticFiles`]
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Directory from which files are served
+    pub root: DocumentRoot,
+    /// Files served, in order of preference, when a directory is requested
+    pub index_files: Vec<String>,
+    /// Renders the content of directories without index file instead of answering 404
+    pub directory_listing: bool,
+    /// Table used to find the MIME type of files from their extension
+    pub mime_types: Arc<MimeTypes>,
+    /// Finds the type of files with an unknown extension from their first bytes. Otherwise they
+    /// are sent as `application/octet-stream`. Disabled by default, as a file uploaded without
+    /// extension could then be served as HTML.
+    pub content_sniffing: bool,
+    /// Sends `X-Content-Type-Options: nosniff`, which prevents browsers from guessing another type
+    /// than the one sent by the server. Enabled by default.
+    pub nosniff: bool,
+    /// Compression of the responses containing compressible files
+    pub compression: CompressionConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            root: DocumentRoot::new("."),
+            index_files: vec![String::from("index.html"), String::from("index.htm")],
+            directory_listing: false,
+            mime_types: Arc::new(MimeTypes::new()),
+            content_sniffing: false,
+            nosniff: true,
+            compression: CompressionConfig::default(),
+        }
+    }
 }
 
 /// HTTP server implementation
@@ -44,14 +128,21 @@ where
 {
     /// Connection used to handle request and provide response
     connection: T,
+    /// Handler producing the response to each request, wrapped in middlewares
+    pipeline: Pipeline,
+    /// Handle stopping the server
+    shutdown: ShutdownHandle,
 }
 
 impl<T: Connection> Server<T> {
-    /// Return a new [`Server`] using the provided connection.
+    /// Return a new [`Server`] using the provided connection and answering requests with the
+    /// provided handler, e.g. a [`Router`] or a closure.
     /// # Example
     ///
     /// ```
     /// use http_server::connection::tcp::TcpServerConnection;
+    /// use http_server::http::request::HttpRequest;
+    /// use http_server::http::response::HttpResponse;
     /// use std::net::SocketAddr;
     /// use std::str::FromStr;
     /// use http_server::http::server::Server;
@@ -63,90 +154,65 @@ impl<T: Connection> Server<T> {
     ///     .expect("Unable to initialize connection. Server shutdown");
     ///
     /// // Create new server
-    /// let http_server = Server::new(tcp_server_connection);
+    /// let http_server = Server::new(tcp_server_connection, |request: &HttpRequest| {
+    ///     Ok(HttpResponse::builder().body(request.path()).build())
+    /// });
     /// ```
-    pub fn new(connection: T) -> Server<T> {
-        Server { connection }
+    pub fn new<H: Handler + 'static>(connection: T, handler: H) -> Server<T> {
+        Server {
+            connection,
+            pipeline: Pipeline::new(handler),
+            shutdown: ShutdownHandle::new(),
+        }
     }
 
-    /// Start listening to incoming Http request
-    pub fn run(&self) {
-        self.connection
-            .listen(|request| Self::request_handler(request));
-    }
-
-    /// Handles HTTP request, used internally by the server as the callback for the connection.
-    fn request_handler(request: &[u8]) -> Result<Message, ServerError> {
-        std::str::from_utf8(request)
-            .map_or_else(
-                |_| {
-                    Err(ServerError::new(
-                        "Unable to convert request to utf8 format. Request rejected",
-                    ))
-                },
-                |request| Ok(HttpRequest::from_str(request)),
-            )?
-            .map_or_else(
-                |_| Ok(Self::build_not_implemented_response()),
-                |http_request| match http_request.line.method {
-                    HttpMethod::Get => Self::handle_get_request(&http_request),
-                },
-            )
-    }
-
-    /// Handles GET request and returns corresponding response
-    fn handle_get_request(request: &HttpRequest) -> Result<Message, ServerError> {
-        let mime = find_mimetype(&request.line.uri[1..]);
-
-        load_content_from_uri(&request.line.uri[1..]).map_or_else(
-            |_| Ok(Self::build_not_found_response()),
-            |content| {
-                let response = Self::build_http_response(200).unwrap();
-                let content_type = build_content_type(&mime);
-                let blank_line = "\r\n";
-                let mut message = Vec::new();
-                message.extend_from_slice(response.as_bytes());
-                message.extend_from_slice(content_type.as_bytes());
-                message.extend_from_slice(blank_line.as_bytes());
-                message.extend_from_slice(&content);
-                Ok(message)
+    /// Return a new [`Server`] using the provided connection and serving the files below `root`.
+    /// Requests for paths outside of `root` are rejected.
+    pub fn with_document_root<P: Into<PathBuf>>(connection: T, root: P) -> Server<T> {
+        Self::with_config(
+            connection,
+            ServerConfig {
+                root: DocumentRoot::new(root),
+                ..ServerConfig::default()
             },
         )
     }
 
-    /// Generate a Not Implemented response
-    fn build_not_implemented_response() -> Message {
-        format!("{}\r\n", Self::build_http_response(501).unwrap()).into_bytes()
+    /// Return a new [`Server`] using the provided connection and serving files with the
+    /// provided configuration.
+    pub fn with_config(connection: T, config: ServerConfig) -> Server<T> {
+        Self::new(
+            connection,
+            Router::new().mount("/", StaticFiles::new(config)),
+        )
     }
 
-    /// Generate a Not Found response. Use user-defined 404.html page if found, else returns default one.
-    fn build_not_found_response() -> Message {
-        load_content_from_uri("404.html").map_or_else(
-            |_| {
-                format!(
-                    "{}\r\n404 - Page not found",
-                    Self::build_http_response(404).unwrap()
-                )
-                .into_bytes()
-            },
-            |content| {
-                let response = Self::build_http_response(404).unwrap();
-                let blank_line = "\r\n";
-                let mut message = Vec::new();
-                message.extend_from_slice(response.as_bytes());
-                message.extend_from_slice(blank_line.as_bytes());
-                message.extend_from_slice(&content);
-                message
-            },
-        )
+    /// Wraps the handling of requests with a middleware. Middlewares run in the order they are
+    /// added: the first one sees the request first and the response last, see [`Pipeline`].
+    pub fn layer<M: Middleware + 'static>(mut self, middleware: M) -> Server<T> {
+        self.pipeline = self.pipeline.layer(middleware);
+        self
     }
 
-    /// Build HTTP response header
-    fn build_http_response(status_code: u16) -> Result<String, ServerError> {
-        StatusCode::from_u16(status_code).map_or_else(
-            |_| Err(ServerError::new("Unknown status code")),
-            |code| Ok(format!("HTTP/1.1 {} {}\r\n", status_code, code.as_str())),
-        )
+    /// Wraps the handling of requests with a closure taking the request and [`Next`].
+    pub fn layer_fn<F>(self, middleware: F) -> Server<T>
+    where
+        F: Fn(&HttpRequest, Next) -> Result<HttpResponse, ServerError> + Send + Sync + 'static,
+    {
+        self.layer(middleware)
+    }
+
+    /// Returns a handle stopping the server gracefully. It can be used from another thread, or
+    /// from a signal handler, while [`Server::run`] is blocked.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Start listening to incoming Http request. Returns once shutdown is requested through
+    /// [`Server::shutdown_handle`] and the requests being served are finished.
+    pub fn run(&self) {
+        self.connection
+            .listen(Arc::new(self.pipeline.clone()), self.shutdown.clone());
     }
 }
 
@@ -154,9 +220,11 @@ impl<T: Connection> Server<T> {
 mod tests {
     use super::*;
     use std::cell::RefCell;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     struct TestConnection {
-        pull_message: Vec<Vec<u8>>,
+        pull_message: Vec<HttpRequest>,
         push_message: RefCell<Vec<Vec<u8>>>,
     }
 
@@ -164,9 +232,9 @@ mod tests {
         fn new() -> TestConnection {
             TestConnection {
                 pull_message: vec![
-                    String::from("1").as_bytes().to_vec(),
-                    String::from("2").as_bytes().to_vec(),
-                    String::from("3").as_bytes().to_vec(),
+                    HttpRequest::from_str("GET /1 HTTP/1.1\r\n\r\n").unwrap(),
+                    HttpRequest::from_str("GET /2 HTTP/1.1\r\n\r\n").unwrap(),
+                    HttpRequest::from_str("GET /3 HTTP/1.1\r\n\r\n").unwrap(),
                 ],
                 push_message: RefCell::new(vec![]),
             }
@@ -174,32 +242,106 @@ mod tests {
     }
 
     impl Connection for TestConnection {
-        fn listen<T: 'static + Copy + Fn(&[u8]) -> Result<Message, ServerError> + Send + Sync>(
-            &self,
-            callback: T,
-        ) {
+        fn listen<H: Handler + ?Sized + 'static>(&self, handler: Arc<H>, _: ShutdownHandle) {
+            let response = handler.handle(&self.pull_message[0]).unwrap();
             self.push_message
                 .borrow_mut()
-                .push((callback)(&self.pull_message[0]).unwrap());
+                .push(response.into_bytes().unwrap());
         }
     }
 
     #[test]
     fn pull_message() {
         let test_connection = TestConnection::new();
-        test_connection.listen(|_| Ok(String::from("Test").as_bytes().to_vec()));
-        assert_eq!(
-            String::from("Test").as_bytes().to_vec(),
-            test_connection.push_message.borrow()[0]
+        test_connection.listen(
+            Arc::new(|_: &HttpRequest| Ok(HttpResponse::builder().body("Test").build())),
+            ShutdownHandle::new(),
+        );
+        assert!(test_connection.push_message.borrow()[0].ends_with(b"\r\n\r\nTest"));
+    }
+
+    #[test]
+    fn dispatch_requests_with_router() {
+        let router = Router::new().get("/:number", |_, params| {
+            Ok(HttpResponse::builder()
+                .body(format!("Number {}", params.get("number").unwrap_or("")).as_str())
+                .build())
+        });
+        let server = Server::new(TestConnection::new(), router);
+
+        server.run();
+
+        assert!(server.connection.push_message.borrow()[0].ends_with(b"\r\n\r\nNumber 1"));
+    }
+
+    struct CountingHandler {
+        hits: Arc<AtomicUsize>,
+    }
+
+    impl Handler for CountingHandler {
+        fn handle(&self, request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+            let hits = self.hits.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(HttpResponse::builder()
+                .body(format!("{} {}", request.path(), hits).as_str())
+                .build())
+        }
+    }
+
+    #[test]
+    fn share_handler_state_between_requests() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let server = Server::new(
+            TestConnection::new(),
+            CountingHandler {
+                hits: Arc::clone(&hits),
+            },
         );
+
+        server.run();
+        server.run();
+
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(server.connection.push_message.borrow()[1].ends_with(b"\r\n\r\n/1 2"));
+    }
+
+    #[test]
+    fn answer_with_closure() {
+        let server = Server::new(TestConnection::new(), |request: &HttpRequest| {
+            Ok(HttpResponse::builder().body(request.path()).build())
+        });
+
+        server.run();
+
+        assert!(server.connection.push_message.borrow()[0].ends_with(b"\r\n\r\n/1"));
+    }
+
+    #[test]
+    fn wrap_handler_with_layers() {
+        let server = Server::new(TestConnection::new(), |request: &HttpRequest| {
+            Ok(HttpResponse::builder().body(request.path()).build())
+        })
+        .layer_fn(|request, next| {
+            let response = next.run(request)?;
+            Ok(HttpResponse::builder()
+                .body(format!("<{}>", response.status()).as_str())
+                .build())
+        })
+        .layer_fn(|request, next| match request.path() {
+            "/1" => Ok(HttpResponse::new(http::StatusCode::FORBIDDEN)),
+            _ => next.run(request),
+        });
+
+        server.run();
+
+        assert!(server.connection.push_message.borrow()[0].ends_with(b"\r\n\r\n<403 Forbidden>"));
     }
 
     #[test]
-    fn test_load_non_existing_png_file() {
-        let uri = "non_existing.png";
+    fn serve_files_by_default() {
+        let server = Server::with_document_root(TestConnection::new(), "example");
 
-        let result = load_content_from_uri(&uri);
+        server.run();
 
-        assert!(result.is_err());
+        assert!(server.connection.push_message.borrow()[0].starts_with(b"HTTP/1.1 404"));
     }
 }
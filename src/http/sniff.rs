@@ -0,0 +1,174 @@
+use mime::Mime;
+
+/// Number of bytes at the start of a file which are enough to find its type
+pub const SNIFF_LENGTH: usize = 512;
+
+/// Signatures found at the start of binary formats, as listed by the WHATWG MIME sniffing
+/// standard
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b\x08", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"Rar!\x1a\x07", "application/vnd.rar"),
+    (b"\x00asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"OggS\x00", "application/ogg"),
+    (b"ID3", "audio/mpeg"),
+    (b"fLaC", "audio/flac"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"%!PS-Adobe-", "application/postscript"),
+];
+
+/// Tags starting an HTML document, compared case-insensitively after leading whitespace
+const HTML_TAGS: &[&[u8]] = &[
+    b"<!doctype html",
+    b"<html",
+    b"<head",
+    b"<body",
+    b"<script",
+    b"<iframe",
+    b"<h1",
+    b"<div",
+    b"<font",
+    b"<table",
+    b"<a",
+    b"<style",
+    b"<title",
+    b"<b",
+    b"<br",
+    b"<p",
+    b"<!--",
+];
+
+/// Returns true if the data starts with an HTML tag. The tag must be followed by a space or
+/// `>` so that `<a` does not match `<abc>`.
+fn is_html(data: &[u8]) -> bool {
+    let start = data
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let data = &data[start..];
+
+    HTML_TAGS.iter().any(|tag| {
+        data.len() > tag.len()
+            && data[..tag.len()].eq_ignore_ascii_case(tag)
+            && (tag == b"<!--" || matches!(data[tag.len()], b' ' | b'>'))
+    })
+}
+
+/// Returns true if the data looks like text: it is valid UTF-8, except possibly for a truncated
+/// last character, and does not contain control characters other than whitespace.
+fn is_text(data: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(data) {
+        Ok(_) => true,
+        // Data may stop in the middle of a multi-byte character
+        Err(error) => error.error_len().is_none(),
+    };
+    valid
+        && !data
+            .iter()
+            .any(|&byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+}
+
+/// Finds the MIME type of data from its first bytes. Known binary signatures, HTML and XML
+/// documents are recognised. Other data is `text/plain` if it looks like text and
+/// `application/octet-stream` otherwise.
+pub fn sniff(data: &[u8]) -> Mime {
+    let data = &data[..data.len().min(SNIFF_LENGTH)];
+
+    let signature = SIGNATURES
+        .iter()
+        .find(|(signature, _)| data.starts_with(signature));
+    if let Some(mime) = signature.and_then(|(_, mime)| mime.parse().ok()) {
+        return mime;
+    }
+    // RIFF container whose size is followed by the WebP format
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        return "image/webp"
+            .parse()
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+    }
+
+    // Byte order marks identify text, whatever follows them
+    if data.starts_with(b"\xef\xbb\xbf") {
+        return mime::TEXT_PLAIN;
+    }
+    if is_html(data) {
+        return mime::TEXT_HTML;
+    }
+    if data.starts_with(b"<?xml") {
+        return mime::TEXT_XML;
+    }
+    if is_text(data) {
+        return mime::TEXT_PLAIN;
+    }
+    mime::APPLICATION_OCTET_STREAM
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniff_images() {
+        assert_eq!(
+            sniff(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR"),
+            mime::IMAGE_PNG
+        );
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0\x00\x10JFIF"), mime::IMAGE_JPEG);
+        assert_eq!(sniff(b"GIF89a\x01\x00"), mime::IMAGE_GIF);
+        assert_eq!(
+            sniff(b"RIFF\x24\x00\x00\x00WEBPVP8 ").essence_str(),
+            "image/webp"
+        );
+        assert_ne!(
+            sniff(b"RIFF\x24\x00\x00\x00WAVEfmt ").essence_str(),
+            "image/webp"
+        );
+    }
+
+    #[test]
+    fn sniff_documents_and_archives() {
+        assert_eq!(sniff(b"%PDF-1.7\n"), mime::APPLICATION_PDF);
+        assert_eq!(
+            sniff(b"PK\x03\x04\x14\x00").essence_str(),
+            "application/zip"
+        );
+        assert_eq!(sniff(b"\x1f\x8b\x08\x00").essence_str(), "application/gzip");
+    }
+
+    #[test]
+    fn sniff_html_and_xml() {
+        assert_eq!(sniff(b"\n  <!DOCTYPE html>\n<html>"), mime::TEXT_HTML);
+        assert_eq!(sniff(b"<HTML><body>"), mime::TEXT_HTML);
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?>"), mime::TEXT_XML);
+        assert_eq!(sniff(b"<abc>"), mime::TEXT_PLAIN);
+    }
+
+    #[test]
+    fn sniff_text() {
+        assert_eq!(sniff(b"hello world\n"), mime::TEXT_PLAIN);
+        assert_eq!(sniff("héllo".as_bytes()), mime::TEXT_PLAIN);
+        // Last character is cut in the middle
+        assert_eq!(sniff(&"héllo é".as_bytes()[..8]), mime::TEXT_PLAIN);
+        assert_eq!(sniff(b""), mime::TEXT_PLAIN);
+    }
+
+    #[test]
+    fn sniff_binary() {
+        assert_eq!(
+            sniff(b"\x00\x01\x02\x03binary"),
+            mime::APPLICATION_OCTET_STREAM
+        );
+        assert_eq!(sniff(b"text\xff\xfe"), mime::APPLICATION_OCTET_STREAM);
+    }
+}
@@ -0,0 +1,769 @@
+use crate::http::compression::{accepted_encodings, is_compressible, Encoding};
+use crate::http::conditional::{evaluate_preconditions, Precondition, Validators};
+use crate::http::content::{build_content_type, load_content_from_uri, FileContent};
+use crate::http::files::{DocumentRoot, ResolveError};
+use crate::http::listing::{
+    read_entries, render_html, render_json, sort_entries, ListingFormat, ListingOptions,
+};
+use crate::http::range::{
+    generate_boundary, multipart_body, parse_range, unsatisfied_content_range, ByteRange,
+    RangeRequest,
+};
+use crate::http::request::{HttpMethod, HttpRequest};
+use crate::http::response::HttpResponse;
+use crate::http::router::{Params, RouteHandler};
+use crate::http::server::{Handler, ServerConfig, ServerError};
+use crate::http::sniff::{sniff, SNIFF_LENGTH};
+use crate::http::uri::RequestTarget;
+use http::header::{
+    HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, ALLOW, CONTENT_ENCODING, CONTENT_RANGE,
+    CONTENT_TYPE, IF_RANGE, LOCATION, RANGE, VARY, X_CONTENT_TYPE_OPTIONS,
+};
+use http::StatusCode;
+use std::io;
+use std::path::Path;
+
+/// Methods supported by [`StaticFiles`]
+const ALLOWED_METHODS: &str = "GET, HEAD";
+
+/// Representation of a file selected for a request
+struct Representation {
+    /// File sent, which is a precompressed sibling of the requested file if `encoding` is not
+    /// the identity and `on_the_fly` is false
+    content: FileContent,
+    /// Content coding of the body
+    encoding: Encoding,
+    /// Compresses the content while it is sent
+    on_the_fly: bool,
+}
+
+/// Handler serving the files of a document root. It can be mounted on a [`Router`] with
+/// [`Router::mount`], in which case the path below the mount point selects the file.
+///
+/// [`Router`]: crate::http::router::Router
+/// [`Router::mount`]: crate::http::router::Router::mount
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    config: ServerConfig,
+}
+
+impl StaticFiles {
+    /// Creates a new [`StaticFiles`] serving files with the provided configuration.
+    pub fn new(config: ServerConfig) -> StaticFiles {
+        StaticFiles { config }
+    }
+
+    /// Returns the configuration used to serve files.
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    /// Serves the file at `path`, which is percent-encoded and relative to the document root.
+    /// Only GET and HEAD requests are supported.
+    pub fn serve(&self, request: &HttpRequest, path: &str) -> Result<HttpResponse, ServerError> {
+        let config = &self.config;
+        let mut response = match request.line.method {
+            HttpMethod::Get => Self::handle_get_request(config, request, path)?,
+            HttpMethod::Head => Self::handle_get_request(config, request, path)?.omit_body(),
+            _ => Self::build_method_not_allowed_response(),
+        };
+
+        if config.nosniff {
+            response
+                .headers_mut()
+                .insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        }
+        Ok(response)
+    }
+
+    /// Handles GET request and returns corresponding response
+    fn handle_get_request(
+        config: &ServerConfig,
+        request: &HttpRequest,
+        path: &str,
+    ) -> Result<HttpResponse, ServerError> {
+        let root = &config.root;
+        let mut path = match root.resolve(path) {
+            Ok(path) => path,
+            Err(error) => return Ok(Self::build_resolve_error_response(root, &error)),
+        };
+
+        if path.is_dir() {
+            let target = &request.line.target;
+
+            // Relative links of the index page are resolved from the directory itself
+            if !target.path().ends_with('/') {
+                let location = match target.query() {
+                    Some(query) => format!("{}/?{}", target.path(), query),
+                    None => format!("{}/", target.path()),
+                };
+                return Ok(Self::build_redirect_response(&location));
+            }
+
+            match root.find_index(&path, &config.index_files) {
+                Some(index) => path = index,
+                None if config.directory_listing => {
+                    return Ok(Self::build_listing_response(&path, target))
+                }
+                None => return Ok(Self::build_not_found_response(root)),
+            }
+        }
+
+        Self::serve_file(config, request, &path)
+    }
+
+    /// Sends a file, or parts of it, in the encoding preferred by the client. Conditional
+    /// headers are evaluated against the selected representation.
+    fn serve_file(
+        config: &ServerConfig,
+        request: &HttpRequest,
+        path: &Path,
+    ) -> Result<HttpResponse, ServerError> {
+        let root = &config.root;
+        let mut content = match load_content_from_uri(path) {
+            Ok(content) => content,
+            Err(_) => return Ok(Self::build_not_found_response(root)),
+        };
+
+        let mime = match config.mime_types.lookup(&path.to_string_lossy()) {
+            Some(mime) => mime,
+            None if config.content_sniffing => {
+                let prefix = content
+                    .read_prefix(SNIFF_LENGTH)
+                    .map_err(|e| ServerError::new(&e.to_string()))?;
+                sniff(&prefix)
+            }
+            None => mime::APPLICATION_OCTET_STREAM,
+        };
+        let compression = &config.compression;
+        let vary = is_compressible(&mime) && (compression.on_the_fly || compression.precompressed);
+        let representation = if vary {
+            Self::select_representation(config, request, path, content)
+        } else {
+            Representation {
+                content,
+                encoding: Encoding::Identity,
+                on_the_fly: false,
+            }
+        };
+
+        let mut validators = match representation.content.file.metadata() {
+            Ok(metadata) => Validators::from_metadata(&metadata),
+            Err(_) => return Ok(Self::build_not_found_response(root)),
+        };
+        // Strong entity tags must differ between encodings of the same file
+        if let (Some(etag), Encoding::Brotli | Encoding::Gzip | Encoding::Deflate) =
+            (validators.etag.as_mut(), representation.encoding)
+        {
+            etag.tag = format!("{}-{}", etag.tag, representation.encoding.as_str());
+        }
+
+        let mut response =
+            match evaluate_preconditions(&request.line.method, &request.headers, &validators) {
+                Precondition::Passed => Self::build_content_response(
+                    request,
+                    representation,
+                    build_content_type(&mime),
+                    &validators,
+                )
+                .map_err(|e| ServerError::new(&e.to_string()))?,
+                Precondition::NotModified => Self::build_not_modified_response(&validators),
+                Precondition::Failed => HttpResponse::builder()
+                    .status(StatusCode::PRECONDITION_FAILED)
+                    .build(),
+            };
+        if vary {
+            response
+                .headers_mut()
+                .insert(VARY, HeaderValue::from_static("accept-encoding"));
+        }
+        Ok(response)
+    }
+
+    /// Selects the representation of a compressible file sent for a request: a precompressed
+    /// sibling of the file, the file compressed on the fly or the file itself.
+    fn select_representation(
+        config: &ServerConfig,
+        request: &HttpRequest,
+        path: &Path,
+        content: FileContent,
+    ) -> Representation {
+        let compression = &config.compression;
+        // Ranges of content compressed on the fly cannot be located in advance
+        let on_the_fly = compression.on_the_fly
+            && content.length >= compression.min_size
+            && !request.headers.contains_key(RANGE);
+
+        for encoding in accepted_encodings(request.headers.get(ACCEPT_ENCODING)) {
+            if encoding == Encoding::Identity {
+                break;
+            }
+            let sibling = encoding
+                .extension()
+                .filter(|_| compression.precompressed)
+                .and_then(|extension| config.root.find_sibling(path, extension))
+                .and_then(|sibling| load_content_from_uri(sibling).ok());
+            if let Some(sibling) = sibling {
+                return Representation {
+                    content: sibling,
+                    encoding,
+                    on_the_fly: false,
+                };
+            }
+            if on_the_fly {
+                return Representation {
+                    content,
+                    encoding,
+                    on_the_fly: true,
+                };
+            }
+        }
+        Representation {
+            content,
+            encoding: Encoding::Identity,
+            on_the_fly: false,
+        }
+    }
+
+    /// Generate the response containing a representation, or the ranges of it asked by the
+    /// request
+    fn build_content_response(
+        request: &HttpRequest,
+        representation: Representation,
+        content_type: HeaderValue,
+        validators: &Validators,
+    ) -> io::Result<HttpResponse> {
+        let Representation {
+            content,
+            encoding,
+            on_the_fly,
+        } = representation;
+        let length = content.length;
+
+        let mut response = if on_the_fly {
+            HttpResponse::builder()
+                .header(CONTENT_TYPE, content_type)
+                .body(encoding.encode(content.file))
+                .build()
+        } else {
+            match Self::requested_ranges(request, validators, length) {
+                RangeRequest::Ignored => HttpResponse::builder()
+                    .header(CONTENT_TYPE, content_type)
+                    .body(content)
+                    .build(),
+                RangeRequest::Satisfiable(ranges) => {
+                    Self::build_partial_response(content, &ranges, content_type)?
+                }
+                RangeRequest::Unsatisfiable => HttpResponse::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(CONTENT_RANGE, unsatisfied_content_range(length))
+                    .build(),
+            }
+        };
+        if encoding != Encoding::Identity && response.status() != StatusCode::RANGE_NOT_SATISFIABLE
+        {
+            response
+                .headers_mut()
+                .insert(CONTENT_ENCODING, encoding.header_value());
+        }
+        response
+            .headers_mut()
+            .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        validators.add_headers(response.headers_mut());
+        Ok(response)
+    }
+
+    /// Returns the ranges asked by a GET request. The `Range` header is ignored if the `If-Range`
+    /// condition does not match the current representation.
+    fn requested_ranges(
+        request: &HttpRequest,
+        validators: &Validators,
+        length: u64,
+    ) -> RangeRequest {
+        if request.line.method != HttpMethod::Get {
+            return RangeRequest::Ignored;
+        }
+        let range = match request.headers.get(RANGE).map(HeaderValue::to_str) {
+            Some(Ok(range)) => range,
+            _ => return RangeRequest::Ignored,
+        };
+        match request.headers.get(IF_RANGE) {
+            Some(condition) if !validators.matches_if_range(condition) => RangeRequest::Ignored,
+            _ => parse_range(range, length),
+        }
+    }
+
+    /// Generate a Partial Content response. Several ranges are sent as a `multipart/byteranges`
+    /// body.
+    fn build_partial_response(
+        content: FileContent,
+        ranges: &[ByteRange],
+        content_type: HeaderValue,
+    ) -> io::Result<HttpResponse> {
+        let response = HttpResponse::builder().status(StatusCode::PARTIAL_CONTENT);
+        let length = content.length;
+
+        Ok(match ranges {
+            [range] => response
+                .header(CONTENT_TYPE, content_type)
+                .header(CONTENT_RANGE, range.content_range(length))
+                .body(content.into_range_body(range)?)
+                .build(),
+            _ => {
+                let boundary = generate_boundary();
+                let body = multipart_body(content.file, ranges, &content_type, &boundary, length);
+                match HeaderValue::from_str(&format!("multipart/byteranges; boundary={}", boundary))
+                {
+                    Ok(multipart_type) => response.header(CONTENT_TYPE, multipart_type),
+                    Err(_) => response,
+                }
+                .body(body)
+                .build()
+            }
+        })
+    }
+
+    /// Generate a Not Modified response carrying the validators of the representation
+    fn build_not_modified_response(validators: &Validators) -> HttpResponse {
+        let mut response = HttpResponse::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .build();
+        validators.add_headers(response.headers_mut());
+        response
+    }
+
+    /// Generate a Moved Permanently response redirecting to `location`
+    fn build_redirect_response(location: &str) -> HttpResponse {
+        let response = HttpResponse::builder().status(StatusCode::MOVED_PERMANENTLY);
+
+        match HeaderValue::from_str(location) {
+            Ok(location) => response.header(LOCATION, location).build(),
+            Err(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    /// Generate the listing of a directory, in the format selected by the query of the request
+    fn build_listing_response(dir: &Path, target: &RequestTarget) -> HttpResponse {
+        let mut entries = match read_entries(dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                return HttpResponse::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .build()
+            }
+        };
+        let options = ListingOptions::from_query(&target.query_params());
+        sort_entries(&mut entries, &options);
+
+        let (mime, body) = match options.format {
+            ListingFormat::Html => {
+                let title = target.decoded_path().unwrap_or_default();
+                (mime::TEXT_HTML, render_html(&title, &entries, &options))
+            }
+            ListingFormat::Json => (mime::APPLICATION_JSON, render_json(&entries)),
+        };
+
+        HttpResponse::builder()
+            .header(CONTENT_TYPE, build_content_type(&mime))
+            .body(body.as_str())
+            .build()
+    }
+
+    /// Generate the response sent when the target of a request cannot be resolved to a file
+    fn build_resolve_error_response(root: &DocumentRoot, error: &ResolveError) -> HttpResponse {
+        let status = error.status_code();
+        if status == StatusCode::NOT_FOUND {
+            return Self::build_not_found_response(root);
+        }
+
+        HttpResponse::builder()
+            .status(status)
+            .header(CONTENT_TYPE, build_content_type(&mime::TEXT_PLAIN))
+            .body(
+                format!(
+                    "{} - {}",
+                    status.as_str(),
+                    status.canonical_reason().unwrap_or("")
+                )
+                .as_str(),
+            )
+            .build()
+    }
+
+    /// Generate a Method Not Allowed response, listing the supported methods
+    fn build_method_not_allowed_response() -> HttpResponse {
+        HttpResponse::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(ALLOW, HeaderValue::from_static(ALLOWED_METHODS))
+            .build()
+    }
+
+    /// Generate a Not Found response. Use user-defined 404.html page of the document root if
+    /// found, else returns default one.
+    fn build_not_found_response(root: &DocumentRoot) -> HttpResponse {
+        let response = HttpResponse::builder().status(StatusCode::NOT_FOUND);
+
+        match load_content_from_uri(root.path().join("404.html")) {
+            Ok(content) => response
+                .header(CONTENT_TYPE, build_content_type(&mime::TEXT_HTML))
+                .body(content),
+            Err(_) => response
+                .header(CONTENT_TYPE, build_content_type(&mime::TEXT_PLAIN))
+                .body("404 - Page not found"),
+        }
+        .build()
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, request: &HttpRequest) -> Result<HttpResponse, ServerError> {
+        self.serve(request, request.path())
+    }
+}
+
+impl RouteHandler for StaticFiles {
+    fn handle(&self, request: &HttpRequest, params: &Params) -> Result<HttpResponse, ServerError> {
+        let path = format!("/{}", params.get_raw("path").unwrap_or(""));
+        self.serve(request, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::compression::CompressionConfig;
+    use crate::http::mime_types::MimeTypes;
+    use crate::test_utils::empty_test_dir;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    /// Returns the serialized response to a request. Date is fixed so that responses can be
+    /// compared.
+    fn handle(request: &str) -> String {
+        let config = ServerConfig {
+            root: DocumentRoot::new("example"),
+            ..ServerConfig::default()
+        };
+        handle_with(&config, request)
+    }
+
+    fn handle_with(config: &ServerConfig, request: &str) -> String {
+        let request = HttpRequest::from_str(request).unwrap();
+        let mut response = StaticFiles::new(config.clone())
+            .serve(&request, request.path())
+            .unwrap();
+        response.headers_mut().insert(
+            http::header::DATE,
+            HeaderValue::from_static("Thu, 01 Jan 1970 00:00:00 GMT"),
+        );
+        String::from_utf8_lossy(&response.into_bytes().unwrap()).into_owned()
+    }
+
+    #[test]
+    fn head_request_has_no_body() {
+        let get = handle("GET /hello.html HTTP/1.1\r\n\r\n");
+        let head = handle("HEAD /hello.html HTTP/1.1\r\n\r\n");
+
+        assert!(get.starts_with(&head));
+        assert!(head.ends_with("\r\n\r\n"));
+        assert!(head.contains("Content-Length: 159\r\n"));
+    }
+
+    #[test]
+    fn reject_path_outside_document_root() {
+        let response = handle("GET /../Cargo.toml HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+
+    #[test]
+    fn not_found_page_comes_from_document_root() {
+        let response = handle("GET /missing.html HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.contains("Content-Type: text/html; charset=utf-8\r\n"));
+    }
+
+    /// Creates a document root containing `docs/index.htm` and `files/a.txt`.
+    fn directory_config(name: &str, directory_listing: bool) -> ServerConfig {
+        let dir = empty_test_dir(name);
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::create_dir_all(dir.join("files")).unwrap();
+        std::fs::write(dir.join("docs/index.htm"), "index").unwrap();
+        std::fs::write(dir.join("files/a.txt"), "hello").unwrap();
+        ServerConfig {
+            root: DocumentRoot::new(dir),
+            directory_listing,
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn redirect_directory_without_trailing_slash() {
+        let config = directory_config("redirect", false);
+
+        let response = handle_with(&config, "GET /docs?a=1 HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 301 Moved Permanently\r\n"));
+        assert!(response.contains("\r\nLocation: /docs/?a=1\r\n"));
+    }
+
+    #[test]
+    fn serve_index_file_of_directory() {
+        let config = directory_config("index", false);
+
+        let response = handle_with(&config, "GET /docs/ HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nindex"));
+    }
+
+    #[test]
+    fn directory_without_index_is_not_found() {
+        let config = directory_config("no-listing", false);
+
+        let response = handle_with(&config, "GET /files/ HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn list_directory_without_index() {
+        let config = directory_config("listing", true);
+
+        let html = handle_with(&config, "GET /files/ HTTP/1.1\r\n\r\n");
+        let json = handle_with(&config, "GET /files/?format=json HTTP/1.1\r\n\r\n");
+
+        assert!(html.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(html.contains("<a href=\"a.txt\">a.txt</a>"));
+        assert!(json.contains("Content-Type: application/json\r\n"));
+        assert!(json.contains("{\"name\":\"a.txt\",\"type\":\"file\",\"size\":5,"));
+    }
+
+    #[test]
+    fn serve_file_with_query_string() {
+        let response = handle("GET /hello.html?lang=en HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn serve_absolute_form_target() {
+        let response = handle("GET http://localhost:5666/hello.html HTTP/1.1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn use_configured_mime_types() {
+        let mut mime_types = MimeTypes::new();
+        mime_types.load_str("text/x-page html\n");
+        let config = ServerConfig {
+            root: DocumentRoot::new("example"),
+            mime_types: Arc::new(mime_types),
+            ..ServerConfig::default()
+        };
+
+        let response = handle_with(&config, "GET /hello.html HTTP/1.1\r\n\r\n");
+
+        assert!(response.contains("Content-Type: text/x-page; charset=utf-8\r\n"));
+    }
+
+    /// Creates a document root containing files with unknown extensions.
+    fn sniffing_config(name: &str, content_sniffing: bool, nosniff: bool) -> ServerConfig {
+        let dir = empty_test_dir(name);
+        std::fs::write(dir.join("image"), b"\x89PNG\r\n\x1a\n\x00\x00").unwrap();
+        std::fs::write(dir.join("data.unknown"), b"\x00\x01\x02").unwrap();
+        ServerConfig {
+            root: DocumentRoot::new(dir),
+            content_sniffing,
+            nosniff,
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn sniff_type_of_unknown_extension() {
+        let config = sniffing_config("sniffing", true, false);
+
+        let image = handle_with(&config, "GET /image HTTP/1.1\r\n\r\n");
+        let data = handle_with(&config, "GET /data.unknown HTTP/1.1\r\n\r\n");
+
+        assert!(image.contains("Content-Type: image/png\r\n"));
+        assert!(data.contains("Content-Type: application/octet-stream\r\n"));
+        assert!(!image.contains("X-Content-Type-Options"));
+    }
+
+    #[test]
+    fn never_sniff_by_default() {
+        let defaults = ServerConfig::default();
+        let config = sniffing_config(
+            "default-sniffing",
+            defaults.content_sniffing,
+            defaults.nosniff,
+        );
+        std::fs::write(config.root.path().join("upload"), "<html><script>").unwrap();
+
+        let upload = handle_with(&config, "GET /upload HTTP/1.1\r\n\r\n");
+
+        assert!(upload.contains("Content-Type: application/octet-stream\r\n"));
+        assert!(upload.contains("X-Content-Type-Options: nosniff\r\n"));
+    }
+
+    #[test]
+    fn send_nosniff_without_sniffing() {
+        let config = sniffing_config("nosniff", false, true);
+
+        let image = handle_with(&config, "GET /image HTTP/1.1\r\n\r\n");
+
+        assert!(image.contains("Content-Type: application/octet-stream\r\n"));
+        assert!(image.contains("X-Content-Type-Options: nosniff\r\n"));
+    }
+
+    /// Returns the value of a header field of a serialized response.
+    fn header_value<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+        response
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+    }
+
+    #[test]
+    fn send_validators_and_answer_not_modified() {
+        let response = handle("GET /hello.html HTTP/1.1\r\n\r\n");
+        let etag = header_value(&response, "Etag").expect("missing ETag");
+        let last_modified = header_value(&response, "Last-Modified").expect("missing date");
+
+        let not_modified = handle(&format!(
+            "GET /hello.html HTTP/1.1\r\nIf-None-Match: {}\r\n\r\n",
+            etag
+        ));
+        let not_modified_since = handle(&format!(
+            "GET /hello.html HTTP/1.1\r\nIf-Modified-Since: {}\r\n\r\n",
+            last_modified
+        ));
+
+        assert!(not_modified.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+        assert!(not_modified.ends_with("\r\n\r\n"));
+        assert_eq!(header_value(&not_modified, "Etag"), Some(etag));
+        assert!(!not_modified.contains("Content-Length"));
+        assert!(not_modified_since.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+    }
+
+    #[test]
+    fn answer_precondition_failed() {
+        let response = handle("GET /hello.html HTTP/1.1\r\nIf-Match: \"other\"\r\n\r\n");
+        let unmodified = handle(
+            "GET /hello.html HTTP/1.1\r\nIf-Unmodified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n",
+        );
+
+        assert!(response.starts_with("HTTP/1.1 412 Precondition Failed\r\n"));
+        assert!(unmodified.starts_with("HTTP/1.1 412 Precondition Failed\r\n"));
+    }
+
+    #[test]
+    fn answer_single_range() {
+        let response = handle("GET /hello.html HTTP/1.1\r\nRange: bytes=0-14\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(response.contains("Content-Range: bytes 0-14/159\r\n"));
+        assert!(response.contains("Content-Length: 15\r\n"));
+        assert!(response.contains("Accept-Ranges: bytes\r\n"));
+        assert!(response.ends_with("\r\n\r\n<!DOCTYPE html>"));
+    }
+
+    #[test]
+    fn answer_multiple_ranges() {
+        let response = handle("GET /hello.html HTTP/1.1\r\nRange: bytes=0-1, -2\r\n\r\n");
+        let boundary = header_value(&response, "Content-Type")
+            .and_then(|value| value.strip_prefix("multipart/byteranges; boundary="))
+            .expect("missing boundary");
+
+        assert!(response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(response.contains(&format!(
+            "\r\n--{}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Range: bytes 0-1/159\r\n\r\n<!",
+            boundary
+        )));
+        assert!(response.contains("Content-Range: bytes 157-158/159\r\n"));
+        assert!(response.ends_with(&format!("\r\n--{}--\r\n", boundary)));
+    }
+
+    #[test]
+    fn answer_range_not_satisfiable() {
+        let response = handle("GET /hello.html HTTP/1.1\r\nRange: bytes=500-\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 416 Range Not Satisfiable\r\n"));
+        assert!(response.contains("Content-Range: bytes */159\r\n"));
+    }
+
+    #[test]
+    fn ignore_range_when_if_range_does_not_match() {
+        let response =
+            handle("GET /hello.html HTTP/1.1\r\nRange: bytes=0-1\r\nIf-Range: \"old\"\r\n\r\n");
+        let head = handle("HEAD /hello.html HTTP/1.1\r\nRange: bytes=0-1\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Length: 159\r\n"));
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    /// Creates a document root containing compressible files and a precompressed sibling.
+    fn compression_config(name: &str, precompressed: bool) -> ServerConfig {
+        let dir = empty_test_dir(name);
+        std::fs::write(dir.join("large.html"), "<p>hello</p>\n".repeat(200)).unwrap();
+        std::fs::write(dir.join("large.html.gz"), b"\x1f\x8b\x08precompressed").unwrap();
+        std::fs::write(dir.join("small.html"), "<p>hello</p>").unwrap();
+        ServerConfig {
+            root: DocumentRoot::new(dir),
+            compression: CompressionConfig {
+                precompressed,
+                ..CompressionConfig::default()
+            },
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn compress_large_files_on_the_fly() {
+        let config = compression_config("compression", false);
+
+        let large = handle_with(
+            &config,
+            "GET /large.html HTTP/1.1\r\nAccept-Encoding: gzip, br;q=0.5\r\n\r\n",
+        );
+        let small = handle_with(
+            &config,
+            "GET /small.html HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+        );
+        let range = handle_with(
+            &config,
+            "GET /large.html HTTP/1.1\r\nAccept-Encoding: gzip\r\nRange: bytes=0-2\r\n\r\n",
+        );
+
+        assert!(large.contains("Content-Encoding: gzip\r\n"));
+        assert!(large.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(large.contains("Vary: accept-encoding\r\n"));
+        assert!(header_value(&large, "Etag").is_some_and(|etag| etag.ends_with("-gzip\"")));
+        assert!(!small.contains("Content-Encoding"));
+        assert!(small.contains("Vary: accept-encoding\r\n"));
+        assert!(range.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(!range.contains("Content-Encoding"));
+    }
+
+    #[test]
+    fn serve_precompressed_sibling() {
+        let config = compression_config("precompressed", true);
+
+        let response = handle_with(
+            &config,
+            "GET /large.html HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n",
+        );
+        let identity = handle_with(&config, "GET /large.html HTTP/1.1\r\n\r\n");
+
+        assert!(response.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(response.contains("Content-Encoding: gzip\r\n"));
+        assert!(response.contains("Content-Length: 16\r\n"));
+        assert!(response.ends_with("\r\n\r\n\x1f\u{fffd}\x08precompressed"));
+        assert!(identity.contains("Content-Length: 2600\r\n"));
+        assert!(identity.contains("Vary: accept-encoding\r\n"));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::http::request::{HttpMethod, HttpRequestError, HttpRequestErrorKind};
+use std::str::FromStr;
+
+/// Returns the value of a hexadecimal digit.
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|value| value as u8)
+}
+
+/// Decodes the `%XX` escape sequences of a string. Returns `None` if an escape sequence is not
+/// followed by two hexadecimal digits.
+pub fn percent_decode(input: &str) -> Option<Vec<u8>> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+
+    while position < bytes.len() {
+        if bytes[position] == b'%' {
+            let high = hex_value(*bytes.get(position + 1)?)?;
+            let low = hex_value(*bytes.get(position + 2)?)?;
+            decoded.push(high << 4 | low);
+            position += 3;
+        } else {
+            decoded.push(bytes[position]);
+            position += 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Decodes a component of a query, as sent by HTML forms: `+` stands for a space and invalid
+/// escape sequences are kept as they are. Invalid UTF-8 sequences are replaced.
+fn decode_query_component(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let escaped = match bytes[position..] {
+            [b'%', high, low, ..] => hex_value(high).zip(hex_value(low)),
+            _ => None,
+        };
+        match (bytes[position], escaped) {
+            (_, Some((high, low))) => {
+                decoded.push(high << 4 | low);
+                position += 3;
+                continue;
+            }
+            (b'+', None) => decoded.push(b' '),
+            (byte, None) => decoded.push(byte),
+        }
+        position += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Form of a request target, as described in RFC 9112 section 3.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetForm {
+    /// Absolute path followed by an optional query, e.g. `/index.html?lang=en`
+    Origin,
+    /// Absolute URI, e.g. `http://example.com/index.html`, mostly sent to proxies
+    Absolute,
+    /// Host and port, only used with CONNECT, e.g. `example.com:443`
+    Authority,
+    /// `*`, only used with OPTIONS to target the server itself
+    Asterisk,
+}
+
+/// Request target split into its components. Components are kept percent-encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestTarget {
+    form: TargetForm,
+    authority: Option<String>,
+    path: String,
+    query: Option<String>,
+}
+
+impl RequestTarget {
+    /// Splits a request target, whose syntax was checked by the request line parser, into its
+    /// components. Targets of CONNECT requests are always in authority-form.
+    pub fn parse(method: &HttpMethod, target: &str) -> Result<RequestTarget, HttpRequestError> {
+        if *method == HttpMethod::Connect {
+            return Ok(RequestTarget {
+                form: TargetForm::Authority,
+                authority: Some(String::from(target)),
+                path: String::new(),
+                query: None,
+            });
+        }
+        if target == "*" {
+            return Ok(RequestTarget {
+                form: TargetForm::Asterisk,
+                authority: None,
+                path: String::from("*"),
+                query: None,
+            });
+        }
+
+        let (form, authority, rest) = if target.starts_with('/') {
+            (TargetForm::Origin, None, target)
+        } else {
+            let (_scheme, hier_part) = target.split_once(':').ok_or_else(|| {
+                HttpRequestError::new(
+                    HttpRequestErrorKind::InvalidTarget,
+                    "Request target has no scheme",
+                )
+            })?;
+            match hier_part.strip_prefix("//") {
+                Some(hier_part) => {
+                    let end = hier_part.find(['/', '?']).unwrap_or(hier_part.len());
+                    let (authority, rest) = hier_part.split_at(end);
+                    (TargetForm::Absolute, Some(String::from(authority)), rest)
+                }
+                None => (TargetForm::Absolute, None, hier_part),
+            }
+        };
+
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(String::from(query))),
+            None => (rest, None),
+        };
+        // An absolute URI with an empty path targets the root (RFC 9112 section 3.2.2)
+        let path = if path.is_empty() && form == TargetForm::Absolute {
+            "/"
+        } else {
+            path
+        };
+
+        Ok(RequestTarget {
+            form,
+            authority,
+            path: String::from(path),
+            query,
+        })
+    }
+
+    /// Returns the form of the target.
+    pub fn form(&self) -> TargetForm {
+        self.form
+    }
+
+    /// Returns the authority (host and optional port) of absolute-form and authority-form targets.
+    pub fn authority(&self) -> Option<&str> {
+        self.authority.as_deref()
+    }
+
+    /// Returns the percent-encoded path of the target.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the path of the target, percent-decoded. Returns `None` if the path contains an
+    /// invalid escape sequence or is not valid UTF-8 once decoded.
+    pub fn decoded_path(&self) -> Option<String> {
+        percent_decode(&self.path).and_then(|decoded| String::from_utf8(decoded).ok())
+    }
+
+    /// Returns the query of the target, without the leading `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Returns the decoded parameters of the query.
+    pub fn query_params(&self) -> QueryParams {
+        QueryParams::parse(self.query.as_deref().unwrap_or(""))
+    }
+}
+
+/// Parameters of a query, decoded from the `name=value&name=value` format of HTML forms.
+/// A name can hold several values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    params: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    /// Parses a query. Parameters without `=` have an empty value and empty parameters are
+    /// ignored.
+    pub fn parse(query: &str) -> QueryParams {
+        let params = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_query_component(name), decode_query_component(value))
+            })
+            .collect();
+        QueryParams { params }
+    }
+
+    /// Returns the first value of a parameter.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all the values of a parameter, in the order of the query.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.params
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parses the first value of a parameter. Returns `None` if the parameter is missing.
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Option<Result<T, T::Err>> {
+        self.get(name).map(str::parse)
+    }
+
+    /// Returns true if the query contains the parameter.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Returns all the parameters, in the order of the query.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns the number of parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns true if the query does not contain any parameter.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_percent_encoded_string() {
+        assert_eq!(percent_decode("/a%20b%2Fc"), Some(b"/a b/c".to_vec()));
+        assert_eq!(percent_decode("/a+b"), Some(b"/a+b".to_vec()));
+        assert_eq!(percent_decode("/a%2"), None);
+        assert_eq!(percent_decode("/a%zz"), None);
+    }
+
+    #[test]
+    fn parse_origin_form() {
+        let target = RequestTarget::parse(&HttpMethod::Get, "/a%20b/page.html?x=1&y").expect("");
+
+        assert_eq!(target.form(), TargetForm::Origin);
+        assert_eq!(target.authority(), None);
+        assert_eq!(target.path(), "/a%20b/page.html");
+        assert_eq!(target.decoded_path(), Some(String::from("/a b/page.html")));
+        assert_eq!(target.query(), Some("x=1&y"));
+    }
+
+    #[test]
+    fn parse_absolute_form() {
+        let target =
+            RequestTarget::parse(&HttpMethod::Get, "http://example.com:8080/index.html?x=1")
+                .expect("");
+        let empty_path = RequestTarget::parse(&HttpMethod::Get, "http://example.com").expect("");
+
+        assert_eq!(target.form(), TargetForm::Absolute);
+        assert_eq!(target.authority(), Some("example.com:8080"));
+        assert_eq!(target.path(), "/index.html");
+        assert_eq!(target.query(), Some("x=1"));
+        assert_eq!(empty_path.path(), "/");
+    }
+
+    #[test]
+    fn parse_authority_and_asterisk_forms() {
+        let connect = RequestTarget::parse(&HttpMethod::Connect, "example.com:443").expect("");
+        let options = RequestTarget::parse(&HttpMethod::Options, "*").expect("");
+
+        assert_eq!(connect.form(), TargetForm::Authority);
+        assert_eq!(connect.authority(), Some("example.com:443"));
+        assert_eq!(options.form(), TargetForm::Asterisk);
+        assert_eq!(options.query(), None);
+    }
+
+    #[test]
+    fn parse_query_params() {
+        let params = QueryParams::parse("tag=a&tag=b+c&name=J%C3%B6rg&empty&&page=2&bad=%zz");
+
+        assert_eq!(params.get("tag"), Some("a"));
+        assert_eq!(params.get_all("tag").collect::<Vec<_>>(), vec!["a", "b c"]);
+        assert_eq!(params.get("name"), Some("Jörg"));
+        assert_eq!(params.get("empty"), Some(""));
+        assert_eq!(params.get("bad"), Some("%zz"));
+        assert_eq!(params.get_as::<u32>("page"), Some(Ok(2)));
+        assert!(params.get_as::<u32>("name").expect("").is_err());
+        assert_eq!(params.get("missing"), None);
+        assert_eq!(params.len(), 6);
+    }
+}
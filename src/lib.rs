@@ -2,5 +2,8 @@
 pub mod connection;
 /// HTTP protocol implementation (server, request, etc)
 pub mod http;
+/// Helpers shared by the tests
+#[cfg(test)]
+mod test_utils;
 /// Threading module
-mod thread;
+pub mod thread;
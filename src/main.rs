@@ -1,20 +1,60 @@
-use http_server::connection::tcp::TcpServerConnection;
+use http_server::connection::shutdown::ShutdownHandle;
+use http_server::connection::tcp::{TcpServerConfig, TcpServerConnection};
+use http_server::http::access_log::{AccessLog, LogFormat};
 use http_server::http::server::Server;
 use std::env;
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
 
 fn main() {
+    // Diagnostics go to stderr, filtered with RUST_LOG, e.g. RUST_LOG=http_server=debug
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
     let args: Vec<String> = env::args().collect();
 
     let socket = &args[1];
+    // Files are served from the current directory unless another one is specified
+    let root = args.get(2).map_or(".", String::as_str);
 
     // Create connection for the server
-    let tcp_server_connection = TcpServerConnection::new(
+    let tcp_server_connection = TcpServerConnection::with_config(
         SocketAddr::from_str(socket).expect("Specified socket does not exist"),
+        TcpServerConfig {
+            access_log: Some(Arc::new(AccessLog::stdout(LogFormat::Combined))),
+            ..TcpServerConfig::default()
+        },
     )
     .expect("Unable to initialize connection. Server shutdown");
     // Init Http server
-    let http_server = Server::new(tcp_server_connection);
+    let http_server = Server::with_document_root(tcp_server_connection, root);
+    shutdown_on_signals(http_server.shutdown_handle());
     http_server.run();
 }
+
+/// Shuts the server down gracefully on SIGINT or SIGTERM. A second signal exits immediately.
+#[cfg(unix)]
+fn shutdown_on_signals(handle: ShutdownHandle) {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGINT, SIGTERM]).expect("Unable to handle signals");
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            if handle.is_requested() {
+                std::process::exit(128 + signal);
+            }
+            tracing::warn!("Received signal {}, shutting down", signal);
+            handle.shutdown();
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn shutdown_on_signals(_: ShutdownHandle) {}
@@ -0,0 +1,11 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Creates an empty directory named after a test in the temporary directory, removing what a
+/// previous run left in it.
+pub fn empty_test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("http-server-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Unable to create test directory");
+    dir
+}
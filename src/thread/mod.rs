@@ -1 +1,2 @@
+/// Pool of worker threads executing jobs from a bounded queue
 pub mod pool;
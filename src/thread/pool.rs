@@ -1,12 +1,65 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::collections::VecDeque;
+use std::io;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 use std::thread;
+use tracing::{debug, error};
 
-enum Message {
-    NewJob(Job),
-    Terminate,
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Configuration of a [`ThreadPool`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadPoolConfig {
+    /// Number of worker threads. Defaults to the available parallelism of the machine.
+    pub workers: usize,
+    /// Maximum number of jobs waiting for a worker once all the workers are busy
+    pub queue_capacity: usize,
+    /// Size of the stack of the worker threads, in bytes. `None` uses the default of the
+    /// standard library.
+    pub stack_size: Option<usize>,
+    /// Prefix of the names of the worker threads, which are followed by the worker id, e.g.
+    /// `http-worker-0`
+    pub thread_name: String,
 }
 
-type Job = Box<dyn FnOnce() + Send + 'static>;
+impl Default for ThreadPoolConfig {
+    fn default() -> Self {
+        ThreadPoolConfig {
+            workers: thread::available_parallelism().map_or(4, NonZeroUsize::get),
+            queue_capacity: 128,
+            stack_size: None,
+            thread_name: String::from("http-worker"),
+        }
+    }
+}
+
+/// State shared by the pool and its workers
+#[derive(Default)]
+struct Queue {
+    /// Jobs waiting for a worker
+    jobs: VecDeque<Job>,
+    /// Number of workers executing a job
+    busy_workers: usize,
+    /// Set when the workers should stop once the queue is empty
+    terminate: bool,
+}
+
+/// Queue of jobs and the conditions used to wait for it
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified when a job is added or the workers should terminate
+    job_added: Condvar,
+    /// Notified when a job is taken or completed by a worker
+    space_available: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
 
 /// Worker executes Job
 struct Worker {
@@ -21,24 +74,53 @@ impl Worker {
     /// # Arguments
     ///
     /// * `id` - The id of the created Worker
-    /// * `receiver` - The receiver used by the worker to get the job to execute
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            match receiver.lock().unwrap().recv().unwrap() {
-                Message::NewJob(job) => {
-                    println!("Worker {} got a job; executing.", id);
-                    job();
-                }
-                Message::Terminate => {
-                    println!("Worker {} was told to terminate.", id);
-                    break;
+    /// * `shared` - The queue from which the worker gets the job to execute
+    /// * `config` - The configuration of the pool, giving the name and stack size of the thread
+    fn new(id: usize, shared: Arc<Shared>, config: &ThreadPoolConfig) -> io::Result<Worker> {
+        let mut builder = thread::Builder::new().name(format!("{}-{}", config.thread_name, id));
+        if let Some(stack_size) = config.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+
+        let thread = builder.spawn(move || {
+            let mut next_job = Self::next_job(&shared, false);
+            while let Some(job) = next_job {
+                // A panicking job must not stop the worker
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    error!("Job executed by worker {} panicked", id);
                 }
+                next_job = Self::next_job(&shared, true);
             }
-        });
+            debug!("Worker {} was told to terminate.", id);
+        })?;
 
-        Worker {
+        Ok(Worker {
             id,
             thread: Some(thread),
+        })
+    }
+
+    /// Waits for the next job, after the previous one is completed if any. Returns `None` when
+    /// the worker should terminate.
+    fn next_job(shared: &Shared, completed: bool) -> Option<Job> {
+        let mut queue = shared.lock();
+        if completed {
+            queue.busy_workers -= 1;
+            shared.space_available.notify_one();
+        }
+        loop {
+            if let Some(job) = queue.jobs.pop_front() {
+                queue.busy_workers += 1;
+                shared.space_available.notify_one();
+                return Some(job);
+            }
+            if queue.terminate {
+                return None;
+            }
+            queue = shared
+                .job_added
+                .wait(queue)
+                .unwrap_or_else(|error| error.into_inner());
         }
     }
 }
@@ -46,7 +128,9 @@ impl Worker {
 /// Create threads and dispatch closures to be executed on their workers
 pub struct ThreadPool {
     workers: Vec<Worker>,
-    sender: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+    /// Number of jobs accepted beyond the idle workers
+    queue_capacity: usize,
 }
 
 impl ThreadPool {
@@ -56,53 +140,210 @@ impl ThreadPool {
     ///
     /// # Panics
     ///
-    /// The `new` function will panic if the size is zero.
+    /// The `new` function will panic if the size is zero or if a thread cannot be created.
     pub fn new(n_threads: usize) -> ThreadPool {
-        assert!(n_threads > 0);
-
-        // Create a channel to send a job between the src pool and their workers.
-        let (sender, receiver) = mpsc::channel();
+        ThreadPool::with_config(&ThreadPoolConfig {
+            workers: n_threads,
+            ..ThreadPoolConfig::default()
+        })
+        .expect("Unable to create worker thread")
+    }
 
-        // Receiver is meant to be used by only one consumer at a time, therefore we have to use Arc
-        // pointer and Mutex to allow each src to have pointer to the receiver
-        let receiver = Arc::new(Mutex::new(receiver));
+    /// Create a new ThreadPool using the provided configuration.
+    /// Returns std::io::Error if a thread cannot be created.
+    ///
+    /// # Panics
+    ///
+    /// The `with_config` function will panic if the number of workers is zero.
+    pub fn with_config(config: &ThreadPoolConfig) -> io::Result<ThreadPool> {
+        assert!(config.workers > 0);
 
-        let mut workers = Vec::with_capacity(n_threads);
+        let shared = Arc::new(Shared::default());
+        let mut pool = ThreadPool {
+            workers: Vec::with_capacity(config.workers),
+            shared,
+            queue_capacity: config.queue_capacity,
+        };
 
-        for id in 0..n_threads {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+        // Workers already created are stopped by drop if one of them cannot be created
+        for id in 0..config.workers {
+            pool.workers
+                .push(Worker::new(id, Arc::clone(&pool.shared), config)?);
         }
 
-        ThreadPool { workers, sender }
+        Ok(pool)
     }
 
-    /// Execute f
+    /// Execute f. Waits for a job to be taken by a worker if the queue is full.
     pub fn execute<F>(&self, f: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        let job = Box::new(f);
+        let mut queue = self.shared.lock();
+        while self.is_full(&queue) {
+            queue = self
+                .shared
+                .space_available
+                .wait(queue)
+                .unwrap_or_else(|error| error.into_inner());
+        }
+        self.push(queue, Box::new(f));
+    }
 
-        self.sender.send(Message::NewJob(job)).unwrap();
+    /// Execute f unless all the workers are busy and the queue is full, in which case f is
+    /// returned.
+    pub fn try_execute<F>(&self, f: F) -> Result<(), F>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let queue = self.shared.lock();
+        if self.is_full(&queue) {
+            return Err(f);
+        }
+        self.push(queue, Box::new(f));
+        Ok(())
+    }
+
+    /// Returns true if a new job would wait beyond the capacity of the queue. Idle workers take
+    /// a job each.
+    fn is_full(&self, queue: &Queue) -> bool {
+        let idle_workers = self.workers.len() - queue.busy_workers;
+        queue.jobs.len() >= idle_workers + self.queue_capacity
+    }
+
+    fn push(&self, mut queue: MutexGuard<'_, Queue>, job: Job) {
+        queue.jobs.push_back(job);
+        drop(queue);
+        self.shared.job_added.notify_one();
     }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        println!("Sending terminate message to all workers.");
+        debug!("Sending terminate message to all workers.");
 
-        for _ in &self.workers {
-            self.sender.send(Message::Terminate).unwrap();
-        }
+        // Workers finish the jobs already queued before terminating
+        self.shared.lock().terminate = true;
+        self.shared.job_added.notify_all();
 
-        println!("Shutting down all workers.");
+        debug!("Shutting down all workers.");
 
         for worker in &mut self.workers {
-            println!("Shutting down worker {}", worker.id);
+            debug!("Shutting down worker {}", worker.id);
 
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                let _ = thread.join();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn config(workers: usize, queue_capacity: usize) -> ThreadPoolConfig {
+        ThreadPoolConfig {
+            workers,
+            queue_capacity,
+            ..ThreadPoolConfig::default()
+        }
+    }
+
+    #[test]
+    fn execute_jobs_on_named_threads() {
+        let pool = ThreadPool::with_config(&config(2, 0)).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..8 {
+            let sender = sender.clone();
+            pool.execute(move || {
+                let name = thread::current().name().map(String::from);
+                sender.send(name).unwrap();
+            });
+        }
+        drop(pool);
+
+        let mut names: Vec<String> = receiver.try_iter().flatten().collect();
+        names.sort();
+        names.dedup();
+        assert!(!names.is_empty());
+        assert!(names
+            .iter()
+            .all(|name| name == "http-worker-0" || name == "http-worker-1"));
+    }
+
+    #[test]
+    fn run_jobs_in_parallel() {
+        let pool = ThreadPool::with_config(&config(2, 0)).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let (first, second) = (sender.clone(), sender);
+
+        // Each job waits for the other, so they only complete if they run at the same time
+        let (first_done, second_wait) = mpsc::channel();
+        let (second_done, first_wait) = mpsc::channel();
+        pool.execute(move || {
+            first_done.send(()).unwrap();
+            first
+                .send(first_wait.recv_timeout(Duration::from_secs(5)).is_ok())
+                .unwrap();
+        });
+        pool.execute(move || {
+            second_done.send(()).unwrap();
+            second
+                .send(second_wait.recv_timeout(Duration::from_secs(5)).is_ok())
+                .unwrap();
+        });
+
+        assert!(receiver.recv().unwrap());
+        assert!(receiver.recv().unwrap());
+    }
+
+    #[test]
+    fn reject_jobs_when_saturated() {
+        let pool = ThreadPool::with_config(&config(1, 1)).unwrap();
+        let (release, blocked) = mpsc::channel::<()>();
+        let (started, wait_started) = mpsc::channel();
+
+        pool.execute(move || {
+            started.send(()).unwrap();
+            let _ = blocked.recv();
+        });
+        wait_started.recv().unwrap();
+
+        assert!(pool.try_execute(|| ()).is_ok());
+        assert!(pool.try_execute(|| ()).is_err());
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn survive_panicking_jobs() {
+        let pool = ThreadPool::with_config(&config(1, 4)).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        pool.execute(|| panic!("Test panic"));
+        pool.execute(move || sender.send(()).unwrap());
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn configure_stack_size() {
+        let pool = ThreadPool::with_config(&ThreadPoolConfig {
+            stack_size: Some(256 * 1024),
+            ..config(1, 0)
+        })
+        .unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        pool.execute(move || {
+            sender
+                .send(thread::current().name().map(String::from))
+                .unwrap()
+        });
+
+        assert_eq!(receiver.recv().unwrap().as_deref(), Some("http-worker-0"));
+    }
+}